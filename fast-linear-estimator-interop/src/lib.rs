@@ -1,10 +1,12 @@
 
+// these are C entry points: the caller (C#) is responsible for passing valid pointers
+// and lengths, so we don't mark every function taking a pointer as `unsafe`
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use std::slice;
 
-#[cfg(target_arch = "aarch64")]
-use fast_linear_estimator::matrix_arm::MatrixF32;
-#[cfg(target_arch = "x86_64")]
-use fast_linear_estimator::matrix_avx::MatrixF32;
+// the default implementation for the target architecture
+use fast_linear_estimator::{MatrixF32, MatrixF64};
 
 #[no_mangle]
 pub extern "C" fn test_add(a: i32, b: i32) -> i32 {
@@ -12,7 +14,8 @@ pub extern "C" fn test_add(a: i32, b: i32) -> i32 {
 }
 
 // coefficients: columns correspond to outputs; rows correspond to inputs;
-// ordering is COLUMN major (i.e. linear in column, stride for rows)
+// ordering is linear in column, stride for rows (`MatrixF32::from_row_major`), and
+// it's packed straight from the caller's buffer
 #[no_mangle]
 pub extern "C" fn matrix_f32_create(
     num_inputs: usize,
@@ -21,11 +24,9 @@ pub extern "C" fn matrix_f32_create(
     intercepts: *const f32,
 ) -> *mut MatrixF32 {
     let cf = unsafe { slice::from_raw_parts(coefficients, num_outputs * num_inputs) };
-    let rows: Vec<Vec<f32>> = cf.chunks(num_outputs).map(|row| row.to_vec()).collect();
-
     let intercepts = unsafe { slice::from_raw_parts(intercepts, num_outputs) };
 
-    if let Some(matrix) = MatrixF32::create_from_rows(&rows, intercepts) {
+    if let Ok(matrix) = MatrixF32::from_row_major(cf, num_inputs, num_outputs, intercepts) {
         Box::into_raw(Box::new(matrix))
     } else {
         std::ptr::null_mut()
@@ -33,6 +34,9 @@ pub extern "C" fn matrix_f32_create(
 }
 
 // clean up matrix
+/// # Safety
+/// `matrix` must have been returned by `matrix_f32_create`, and must not be used
+/// again after this call.
 #[no_mangle]
 pub unsafe extern "C" fn matrix_avx_f32_delete(matrix: *mut MatrixF32) {
     if !matrix.is_null() {
@@ -59,11 +63,7 @@ pub extern "C" fn matrix_f32_product(
     let res = unsafe { slice::from_raw_parts_mut(results, results_length) };
 
     // perform multiplication
-    if let Some(()) = mat.product(vals, res) {
-        true
-    } else {
-        false
-    }
+    mat.product(vals, res).is_ok()
 }
 
 #[no_mangle]
@@ -85,13 +85,86 @@ pub extern "C" fn matrix_f32_softmax_cumulative(
     let res = unsafe { slice::from_raw_parts_mut(results, results_length) };
 
     // perform multiplication
-    if let Some(()) = mat.product_softmax_cumulative_approx(vals, res) {
-        true
+    mat.product_softmax_cumulative_approx(vals, res).is_ok()
+}
+
+//
+// double precision versions of the above
+//
+
+// coefficients: same ordering as for `matrix_f32_create`
+#[no_mangle]
+pub extern "C" fn matrix_f64_create(
+    num_inputs: usize,
+    num_outputs: usize,
+    coefficients: *const f64,
+    intercepts: *const f64,
+) -> *mut MatrixF64 {
+    let cf = unsafe { slice::from_raw_parts(coefficients, num_outputs * num_inputs) };
+    let intercepts = unsafe { slice::from_raw_parts(intercepts, num_outputs) };
+
+    if let Ok(matrix) = MatrixF64::from_row_major(cf, num_inputs, num_outputs, intercepts) {
+        Box::into_raw(Box::new(matrix))
     } else {
-        false
+        std::ptr::null_mut()
     }
 }
 
+// clean up matrix
+/// # Safety
+/// `matrix` must have been returned by `matrix_f64_create`, and must not be used
+/// again after this call.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_f64_delete(matrix: *mut MatrixF64) {
+    if !matrix.is_null() {
+        drop(Box::from_raw(matrix));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn matrix_f64_product(
+    matrix: *mut MatrixF64,
+    values: *const f64,
+    values_length: usize,
+    results: *mut f64,
+    results_length: usize,
+) -> bool {
+    // check for nulls
+    if matrix.is_null() || values.is_null() || results.is_null() {
+        return false;
+    }
+    // get reference to the matrix, but don't take ownership of it
+    let mat = unsafe { Box::leak(Box::from_raw(matrix)) };
+    // get slices for inputs and outputs
+    let vals = unsafe { slice::from_raw_parts(values, values_length) };
+    let res = unsafe { slice::from_raw_parts_mut(results, results_length) };
+
+    // perform multiplication
+    mat.product(vals, res).is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn matrix_f64_softmax_cumulative(
+    matrix: *mut MatrixF64,
+    values: *const f64,
+    values_length: usize,
+    results: *mut f64,
+    results_length: usize,
+) -> bool {
+    // check for nulls
+    if matrix.is_null() || values.is_null() || results.is_null() {
+        return false;
+    }
+    // get reference to the matrix, but don't take ownership of it
+    let mat = unsafe { Box::leak(Box::from_raw(matrix)) };
+    // get slices for inputs and outputs
+    let vals = unsafe { slice::from_raw_parts(values, values_length) };
+    let res = unsafe { slice::from_raw_parts_mut(results, results_length) };
+
+    // perform multiplication
+    mat.product_softmax_cumulative_approx(vals, res).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -193,4 +266,66 @@ mod tests {
             3
         ));
     }
+
+    #[test]
+    fn matrix_interface_f64() {
+        let coefficients = [[1_f64, 2., 3.], [4., 5., 6.]];
+        let intercepts = [100_f64, 200_f64, 300_f64];
+        let inputs = [1_f64, 2.];
+        let mut results = [0_f64; 3];
+
+        let matrix =
+            super::matrix_f64_create(2, 3, coefficients[0].as_ptr(), intercepts.as_ptr());
+
+        assert!(super::matrix_f64_product(
+            matrix,
+            inputs.as_ptr(),
+            2,
+            results.as_mut_ptr(),
+            3
+        ));
+        assert_eq!(results, [109_f64, 212_f64, 315_f64]);
+
+        // wrong input size
+        assert!(!super::matrix_f64_product(
+            matrix,
+            inputs.as_ptr(),
+            1, // wrong
+            results.as_mut_ptr(),
+            3
+        ));
+
+        // softmax of small values, so the cumulative sum is easy to check
+        let small_inputs = [0.01_f64, 0.02];
+        let zero_intercepts = [0_f64; 3];
+        let small = super::matrix_f64_create(
+            2,
+            3,
+            coefficients[0].as_ptr(),
+            zero_intercepts.as_ptr(),
+        );
+        assert!(super::matrix_f64_softmax_cumulative(
+            small,
+            small_inputs.as_ptr(),
+            2,
+            results.as_mut_ptr(),
+            3
+        ));
+        let expected = [0.09_f64.exp(), 0.12_f64.exp(), 0.15_f64.exp()];
+        assert!((results[2] - expected.iter().sum::<f64>()).abs() < 1e-9);
+
+        unsafe {
+            super::matrix_f64_delete(matrix);
+            super::matrix_f64_delete(small);
+        }
+
+        // null matrix fails rather than panics
+        assert!(!super::matrix_f64_product(
+            std::ptr::null_mut(),
+            inputs.as_ptr(),
+            2,
+            results.as_mut_ptr(),
+            3
+        ));
+    }
 }
@@ -4,16 +4,18 @@ extern crate criterion;
 // TODO: Should be using black_box for constant inputs
 //use criterion::black_box;
 
-use criterion::Criterion;
+use criterion::measurement::WallTime;
+use criterion::{black_box, BenchmarkGroup, BenchmarkId, Criterion};
+use std::time::Duration;
 use ndarray::Array2;
 use rand::prelude::*;
 use rand::Rng;
 
-#[cfg(target_arch = "x86_64")]
-use fast_linear_estimator::matrix_avx::MatrixF32;
-
-#[cfg(target_arch = "aarch64")]
-use fast_linear_estimator::matrix_arm::MatrixF32;
+use fast_linear_estimator::matrix_dispatch::Backend;
+use fast_linear_estimator::{
+    Activation, Averaging, Ensemble, FixedMatrixF32, MatrixBF16, MatrixF16, MatrixF32, MatrixI8,
+    Mlp, Scorer, SparseMatrixF32,
+};
 
 // sizes
 const NUM_INPUT: usize = 20;
@@ -56,9 +58,8 @@ fn bench_logistic(crit: &mut Criterion) {
     }
 
     let mut intercepts = [0f32; NUM_OUTPUT];
-    for op in 0..NUM_OUTPUT {
-        let v = rnd.gen_range(intercept_min, intercept_max);
-        intercepts[op] = v;
+    for intercept in intercepts.iter_mut() {
+        *intercept = rnd.gen_range(intercept_min, intercept_max);
     }
 
     crit.bench_function("choose-input", |b| {
@@ -79,8 +80,8 @@ fn bench_logistic(crit: &mut Criterion) {
             b.iter(|| {
                 let input = input_sets.iter().choose(&mut rnd).unwrap();
 
-                let some = mat.product(&input, &mut output_f32);
-                assert!(some.is_some());
+                let ok = mat.product(input, &mut output_f32);
+                assert!(ok.is_ok());
 
                 output_f32[0]
             })
@@ -92,14 +93,138 @@ fn bench_logistic(crit: &mut Criterion) {
             b.iter(|| {
                 let input = input_sets.iter().choose(&mut rnd).unwrap();
 
-                let some = mat.product_softmax_cumulative_approx(&input, &mut output_f32);
-                assert!(some.is_some());
+                let ok = mat.product_softmax_cumulative_approx(input, &mut output_f32);
+                assert!(ok.is_ok());
 
                 output_f32[0]
             })
         });
     }
 
+    // each implementation on its own, where the CPU supports it
+    for &backend in &[
+        Backend::Avx512,
+        Backend::Avx,
+        Backend::Sse,
+        Backend::Neon,
+        Backend::Scalar,
+    ] {
+        let vec_coeff: Vec<Vec<f32>> = coeff_transpose.iter().map(|r| r.to_vec()).collect();
+        let mat = match MatrixF32::create_with_backend(backend, &vec_coeff, &intercepts) {
+            Ok(mat) => mat,
+            Err(_) => continue,
+        };
+        let name = format!("{:?}", backend).to_lowercase();
+
+        let mut output_f32 = vec![0f32; mat.num_columns];
+        crit.bench_function(&format!("matrix-{}-product", name), |b| {
+            b.iter(|| {
+                let input = input_sets.iter().choose(&mut rnd).unwrap();
+
+                let ok = mat.product(input, &mut output_f32);
+                assert!(ok.is_ok());
+
+                output_f32[0]
+            })
+        });
+
+        let mut output_f32 = vec![0f32; mat.num_columns];
+        crit.bench_function(&format!("matrix-{}-softmax", name), |b| {
+            b.iter(|| {
+                let input = input_sets.iter().choose(&mut rnd).unwrap();
+
+                let ok = mat.product_softmax_cumulative_approx(input, &mut output_f32);
+                assert!(ok.is_ok());
+
+                output_f32[0]
+            })
+        });
+    }
+
+    // every input set in one call, versus looping over them one at a time
+    {
+        let vec_coeff: Vec<Vec<f32>> = coeff_transpose.iter().map(|r| r.to_vec()).collect();
+        let mat = MatrixF32::create_from_rows(&vec_coeff, &intercepts).unwrap();
+        let inputs: Vec<f32> = input_sets.iter().flatten().copied().collect();
+        let mut outputs = vec![0f32; NUM_INPUT_SETS * mat.num_columns];
+
+        crit.bench_function("matrix-rowloop-product", |b| {
+            b.iter(|| {
+                for (input, output) in input_sets.iter().zip(outputs.chunks_mut(NUM_OUTPUT)) {
+                    let ok = mat.product(input, output);
+                    assert!(ok.is_ok());
+                }
+                outputs[0]
+            })
+        });
+
+        crit.bench_function("matrix-batch-product", |b| {
+            b.iter(|| {
+                let ok = mat.product_batch(&inputs, NUM_INPUT_SETS, &mut outputs);
+                assert!(ok.is_ok());
+                outputs[0]
+            })
+        });
+
+        crit.bench_function("matrix-rowloop-softmax", |b| {
+            b.iter(|| {
+                for (input, output) in input_sets.iter().zip(outputs.chunks_mut(NUM_OUTPUT)) {
+                    let ok = mat.product_softmax_cumulative_approx(input, output);
+                    assert!(ok.is_ok());
+                }
+                outputs[0]
+            })
+        });
+
+        crit.bench_function("matrix-batch-softmax", |b| {
+            b.iter(|| {
+                let ok = mat.softmax_cumulative_batch(&inputs, NUM_INPUT_SETS, &mut outputs);
+                assert!(ok.is_ok());
+                outputs[0]
+            })
+        });
+    }
+
+    // large batch split across threads (`cargo bench --features parallel`)
+    #[cfg(feature = "parallel")]
+    {
+        use fast_linear_estimator::parallel::DEFAULT_MIN_CHUNK_ROWS;
+
+        const REPEATS: usize = 40;
+        let vec_coeff: Vec<Vec<f32>> = coeff_transpose.iter().map(|r| r.to_vec()).collect();
+        let mat = MatrixF32::create_from_rows(&vec_coeff, &intercepts).unwrap();
+        let n_rows = NUM_INPUT_SETS * REPEATS;
+        let inputs: Vec<f32> = input_sets
+            .iter()
+            .cycle()
+            .take(n_rows)
+            .flatten()
+            .copied()
+            .collect();
+        let mut outputs = vec![0f32; n_rows * mat.num_columns];
+
+        crit.bench_function("matrix-large-batch-softmax", |b| {
+            b.iter(|| {
+                let ok = mat.softmax_cumulative_batch(&inputs, n_rows, &mut outputs);
+                assert!(ok.is_ok());
+                outputs[0]
+            })
+        });
+
+        crit.bench_function("matrix-large-par-batch-softmax", |b| {
+            b.iter(|| {
+                let ok = mat.par_softmax_cumulative_batch(
+                    &inputs,
+                    n_rows,
+                    &mut outputs,
+                    DEFAULT_MIN_CHUNK_ROWS,
+                );
+                assert!(ok.is_ok());
+                outputs[0]
+            })
+        });
+    }
+
     // directly implemented with iterators
     {
         // directly implemented with iterators
@@ -210,13 +335,468 @@ fn bench_logistic(crit: &mut Criterion) {
     }
 }
 
+// the same input and output sizes as `EstimatorBenchSizeVariations` on the C# side;
+// run just these with `cargo bench -- matrix-sizes`
+fn bench_sizes(crit: &mut Criterion) {
+    const SIZES: [usize; 11] = [2, 3, 4, 6, 8, 10, 15, 20, 30, 50, 100];
+
+    let mut rnd = rand::thread_rng();
+    let mut group = crit.benchmark_group("matrix-sizes-softmax");
+    group
+        .warm_up_time(Duration::from_millis(300))
+        .measurement_time(Duration::from_millis(1000));
+
+    for &num_inputs in &SIZES {
+        for &num_outputs in &SIZES {
+            let rows: Vec<Vec<f32>> = (0..num_inputs)
+                .map(|_| (0..num_outputs).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+                .collect();
+            let intercepts: Vec<f32> = (0..num_outputs).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+            let input: Vec<f32> = (0..num_inputs).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+
+            let mat = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+            let mut output = vec![0f32; num_outputs];
+            let id = BenchmarkId::from_parameter(format!("{}x{}", num_inputs, num_outputs));
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    let ok = mat.product_softmax_cumulative_approx(&input, &mut output);
+                    assert!(ok.is_ok());
+                    output[0]
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+// wide outputs, where the product kernels work on several column chunks at once;
+// each backend that's supported here is run, so the narrow and wide intrinsics can
+// be compared. Run just these with `cargo bench -- matrix-wide`
+fn bench_wide(crit: &mut Criterion) {
+    const INPUTS: [usize; 3] = [16, 64, 256];
+    const OUTPUTS: [usize; 5] = [32, 64, 128, 256, 512];
+
+    let mut rnd = rand::thread_rng();
+    let mut group = crit.benchmark_group("matrix-wide-product");
+    group
+        .warm_up_time(Duration::from_millis(300))
+        .measurement_time(Duration::from_millis(1000));
+
+    for &num_inputs in &INPUTS {
+        for &num_outputs in &OUTPUTS {
+            let rows: Vec<Vec<f32>> = (0..num_inputs)
+                .map(|_| (0..num_outputs).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+                .collect();
+            let intercepts: Vec<f32> = (0..num_outputs).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+            let input: Vec<f32> = (0..num_inputs).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+            let mut output = vec![0f32; num_outputs];
+
+            let size = format!("{}x{}", num_inputs, num_outputs);
+            for &backend in &[
+                Backend::Avx512,
+                Backend::Avx,
+                Backend::Sse,
+                Backend::Neon,
+                Backend::Scalar,
+            ] {
+                let mat = match MatrixF32::create_with_backend(backend, &rows, &intercepts) {
+                    Ok(mat) => mat,
+                    Err(_) => continue,
+                };
+                let id = BenchmarkId::new(format!("{:?}", backend), &size);
+                group.bench_function(id, |b| {
+                    b.iter(|| {
+                        let ok = mat.product(black_box(&input), &mut output);
+                        assert!(ok.is_ok());
+                        output[0]
+                    })
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+// wide, mostly-zero inputs (e.g. one-hot encoded categories), at several
+// densities; `dense` is the ordinary product on the same input for comparison
+fn bench_sparse(crit: &mut Criterion) {
+    const NUM_SPARSE_INPUT: usize = 200;
+
+    let mut rnd = rand::thread_rng();
+    let rows: Vec<Vec<f32>> = (0..NUM_SPARSE_INPUT)
+        .map(|_| (0..NUM_OUTPUT).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+        .collect();
+    let intercepts: Vec<f32> = (0..NUM_OUTPUT).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+    let mat = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+    let mut output = vec![0f32; NUM_OUTPUT];
+
+    let mut group = crit.benchmark_group("matrix-sparse-softmax");
+    for &density in &[0.01f64, 0.05, 0.2, 0.5] {
+        let indices: Vec<u32> = (0..NUM_SPARSE_INPUT as u32)
+            .filter(|_| rnd.gen_bool(density))
+            .collect();
+        let values: Vec<f32> = indices.iter().map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+        let mut dense = vec![0f32; NUM_SPARSE_INPUT];
+        for (i, v) in indices.iter().zip(&values) {
+            dense[*i as usize] = *v;
+        }
+
+        group.bench_function(BenchmarkId::new("sparse", density), |b| {
+            b.iter(|| {
+                let ok = mat.softmax_cumulative_sparse(&indices, &values, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("dense", density), |b| {
+            b.iter(|| {
+                let ok = mat.product_softmax_cumulative_approx(&dense, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
+// wide models with mostly-zero coefficients (e.g. L1 regularised), stored sparse
+// versus dense, at several densities
+fn bench_sparse_coefficients(crit: &mut Criterion) {
+    const NUM_WIDE_INPUT: usize = 2000;
+
+    let mut rnd = rand::thread_rng();
+    let input: Vec<f32> = (0..NUM_WIDE_INPUT)
+        .map(|_| rnd.gen_range(-2.5, 2.5))
+        .collect();
+    let intercepts: Vec<f32> = (0..NUM_OUTPUT).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+    let mut output = vec![0f32; NUM_OUTPUT];
+
+    let mut group = crit.benchmark_group("matrix-sparse-coeff-softmax");
+    for &density in &[0.002f64, 0.01, 0.05] {
+        let rows: Vec<Vec<f32>> = (0..NUM_WIDE_INPUT)
+            .map(|_| {
+                (0..NUM_OUTPUT)
+                    .map(|_| match rnd.gen_bool(density) {
+                        true => rnd.gen_range(-0.05, 0.05),
+                        false => 0.0,
+                    })
+                    .collect()
+            })
+            .collect();
+        let sparse = SparseMatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        let dense = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+
+        group.bench_function(BenchmarkId::new("sparse", density), |b| {
+            b.iter(|| {
+                let ok = sparse.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("dense", density), |b| {
+            b.iter(|| {
+                let ok = dense.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_quantized(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+
+    let mut group = crit.benchmark_group("matrix-i8-softmax");
+    for &(num_input, num_output) in &[(100usize, 20usize), (2000, 100), (20000, 200)] {
+        let rows: Vec<Vec<f32>> = (0..num_input)
+            .map(|_| (0..num_output).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+            .collect();
+        let intercepts: Vec<f32> = (0..num_output).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+        let input: Vec<f32> = (0..num_input).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+        let mut output = vec![0f32; num_output];
+
+        let quantized = MatrixI8::create_from_rows(&rows, &intercepts).unwrap();
+        let dense = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+
+        let size = format!("{}x{}", num_input, num_output);
+        group.bench_function(BenchmarkId::new("i8", &size), |b| {
+            b.iter(|| {
+                let ok = quantized.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("f32", &size), |b| {
+            b.iter(|| {
+                let ok = dense.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_half(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+
+    let mut group = crit.benchmark_group("matrix-half-softmax");
+    for &(num_input, num_output) in &[(100usize, 20usize), (2000, 100), (20000, 200)] {
+        let rows: Vec<Vec<f32>> = (0..num_input)
+            .map(|_| (0..num_output).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+            .collect();
+        let intercepts: Vec<f32> = (0..num_output).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+        let input: Vec<f32> = (0..num_input).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+        let mut output = vec![0f32; num_output];
+
+        let f16 = MatrixF16::create_from_rows(&rows, &intercepts).unwrap();
+        let bf16 = MatrixBF16::create_from_rows(&rows, &intercepts).unwrap();
+        let dense = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+
+        let size = format!("{}x{}", num_input, num_output);
+        group.bench_function(BenchmarkId::new("f16", &size), |b| {
+            b.iter(|| {
+                let ok = f16.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("bf16", &size), |b| {
+            b.iter(|| {
+                let ok = bf16.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("f32", &size), |b| {
+            b.iter(|| {
+                let ok = dense.product_softmax_cumulative_approx(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
+// one shape of the fixed size comparison; the shape has to be known at compile time.
+// The input goes through `black_box`, otherwise the whole (inlined) fixed size
+// product is hoisted out of the loop.
+fn bench_fixed_shape<const IN: usize, const OUT: usize>(
+    group: &mut BenchmarkGroup<WallTime>,
+    rnd: &mut ThreadRng,
+) {
+    let rows: Vec<Vec<f32>> = (0..IN)
+        .map(|_| (0..OUT).map(|_| rnd.gen_range(-0.05, 0.05)).collect())
+        .collect();
+    let intercepts: Vec<f32> = (0..OUT).map(|_| rnd.gen_range(-0.01, 0.01)).collect();
+    let mut input = [0f32; IN];
+    input.iter_mut().for_each(|v| *v = rnd.gen_range(-2.5, 2.5));
+
+    let fixed = FixedMatrixF32::<IN, OUT>::create_from_rows(&rows, &intercepts).unwrap();
+    let dense = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+
+    let size = format!("{}x{}", IN, OUT);
+    let mut fixed_output = [0f32; OUT];
+    group.bench_function(BenchmarkId::new("fixed", &size), |b| {
+        b.iter(|| {
+            fixed.product_softmax_cumulative_approx(black_box(&input), &mut fixed_output);
+            fixed_output[0]
+        })
+    });
+    let mut output = vec![0f32; OUT];
+    group.bench_function(BenchmarkId::new("f32", &size), |b| {
+        b.iter(|| {
+            let ok = dense.product_softmax_cumulative_approx(black_box(&input), &mut output);
+            assert!(ok.is_ok());
+            output[0]
+        })
+    });
+}
+
+fn bench_fixed(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+
+    let mut group = crit.benchmark_group("matrix-fixed-softmax");
+    bench_fixed_shape::<4, 3>(&mut group, &mut rnd);
+    bench_fixed_shape::<12, 9>(&mut group, &mut rnd);
+    bench_fixed_shape::<20, 20>(&mut group, &mut rnd);
+    bench_fixed_shape::<64, 16>(&mut group, &mut rnd);
+    group.finish();
+}
+
+fn random_layer(rnd: &mut ThreadRng, num_inputs: usize, num_outputs: usize) -> MatrixF32 {
+    let rows: Vec<Vec<f32>> = (0..num_inputs)
+        .map(|_| (0..num_outputs).map(|_| rnd.gen_range(-0.2, 0.2)).collect())
+        .collect();
+    let intercepts: Vec<f32> = (0..num_outputs).map(|_| rnd.gen_range(-0.1, 0.1)).collect();
+    MatrixF32::create_from_rows(&rows, &intercepts).unwrap()
+}
+
+// two layer networks, against the single linear layer with the same inputs and
+// outputs
+fn bench_mlp(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+    let input: Vec<f32> = (0..NUM_INPUT).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+    let mut output = vec![0f32; NUM_OUTPUT];
+
+    let mut group = crit.benchmark_group("mlp-softmax");
+    let linear = random_layer(&mut rnd, NUM_INPUT, NUM_OUTPUT);
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            let ok = linear.product_softmax_cumulative_approx(black_box(&input), &mut output);
+            assert!(ok.is_ok());
+            output[0]
+        })
+    });
+    for &hidden in &[16, 64] {
+        for &activation in &[Activation::Relu, Activation::Tanh] {
+            let mut net = Mlp::create(
+                vec![(random_layer(&mut rnd, NUM_INPUT, hidden), activation)],
+                random_layer(&mut rnd, hidden, NUM_OUTPUT),
+            )
+            .unwrap();
+            let id = BenchmarkId::new(format!("{:?}", activation), hidden);
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    let ok = net.product_softmax_cumulative_approx(black_box(&input), &mut output);
+                    assert!(ok.is_ok());
+                    output[0]
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+// ten members, each averaging, against scoring the members one at a time and
+// normalising and averaging by hand
+fn bench_ensemble(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+    let input: Vec<f32> = (0..NUM_INPUT).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+    let mut output = vec![0f32; NUM_OUTPUT];
+
+    let members: Vec<MatrixF32> = (0..10)
+        .map(|_| random_layer(&mut rnd, NUM_INPUT, NUM_OUTPUT))
+        .collect();
+
+    let mut group = crit.benchmark_group("ensemble");
+    let mut member_output = vec![0f32; NUM_OUTPUT];
+    group.bench_function("by-hand", |b| {
+        b.iter(|| {
+            output.iter_mut().for_each(|o| *o = 0f32);
+            for member in &members {
+                let ok =
+                    member.product_softmax_cumulative_approx(black_box(&input), &mut member_output);
+                assert!(ok.is_ok());
+                let scale = 0.1 / member_output[NUM_OUTPUT - 1];
+                let mut previous = 0f32;
+                for (o, m) in output.iter_mut().zip(&member_output) {
+                    *o += (m - previous) * scale;
+                    previous = *m;
+                }
+            }
+            output[0]
+        })
+    });
+
+    let mut ensemble = Ensemble::create(members).unwrap();
+    for &averaging in &[Averaging::Probabilities, Averaging::Logits] {
+        group.bench_function(format!("{:?}", averaging), |b| {
+            b.iter(|| {
+                let ok = ensemble.probabilities(black_box(&input), &mut output, averaging);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
+// a few outputs of a wide model, against all of them
+fn bench_selected(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+    let input: Vec<f32> = (0..NUM_INPUT).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+    let num_outputs = 512;
+    let mat = random_layer(&mut rnd, NUM_INPUT, num_outputs);
+
+    let mut group = crit.benchmark_group("matrix-selected");
+    let mut output = vec![0f32; num_outputs];
+    group.bench_function("all", |b| {
+        b.iter(|| {
+            let ok = mat.product(black_box(&input), &mut output);
+            assert!(ok.is_ok());
+            output[0]
+        })
+    });
+    let indices = [3, 150, 151, 400];
+    let mut selected = [0f32; 4];
+    group.bench_function("four", |b| {
+        b.iter(|| {
+            let ok = mat.product_selected(black_box(&input), &indices, &mut selected);
+            assert!(ok.is_ok());
+            selected[0]
+        })
+    });
+    group.bench_function("one", |b| {
+        b.iter(|| mat.product_output(black_box(&input), 150).unwrap())
+    });
+    group.finish();
+}
+
+// changing one input and re-scoring, against the full product
+fn bench_scorer(crit: &mut Criterion) {
+    let mut rnd = rand::thread_rng();
+
+    let mut group = crit.benchmark_group("scorer");
+    for &num_inputs in &[20, 200] {
+        let mut input: Vec<f32> = (0..num_inputs).map(|_| rnd.gen_range(-2.5, 2.5)).collect();
+        let mat = random_layer(&mut rnd, num_inputs, NUM_OUTPUT);
+        let mut output = vec![0f32; NUM_OUTPUT];
+        let size = format!("{}x{}", num_inputs, NUM_OUTPUT);
+
+        let mut scorer = Scorer::create(&mat, &input).unwrap();
+        let mut step = 0;
+        group.bench_function(BenchmarkId::new("incremental", &size), |b| {
+            b.iter(|| {
+                step = (step + 1) % num_inputs;
+                let ok = scorer.set_input(step, black_box(step as f32 * 0.01));
+                assert!(ok.is_ok());
+                scorer.outputs()[0]
+            })
+        });
+        group.bench_function(BenchmarkId::new("product", &size), |b| {
+            b.iter(|| {
+                step = (step + 1) % num_inputs;
+                input[step] = black_box(step as f32 * 0.01);
+                let ok = mat.product(&input, &mut output);
+                assert!(ok.is_ok());
+                output[0]
+            })
+        });
+    }
+    group.finish();
+}
+
 // long form, with samples specified
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
     targets =
         //example_benchmark,
-        bench_logistic
+        bench_logistic,
+        bench_sizes,
+        bench_wide,
+        bench_sparse,
+        bench_sparse_coefficients,
+        bench_quantized,
+        bench_half,
+        bench_fixed,
+        bench_mlp,
+        bench_ensemble,
+        bench_selected,
+        bench_scorer
 }
 
 criterion_main!(benches);
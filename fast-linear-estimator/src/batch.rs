@@ -0,0 +1,38 @@
+// helpers shared by the batched products
+//
+// a batch is `n_rows` input vectors packed one after the other (row major), and
+// the outputs are written the same way: `n_rows` output vectors of `num_outputs`
+
+use crate::error::{check_lengths, EstimatorError};
+
+// number of input vectors processed together by the SIMD batch kernels; each one
+// needs its own accumulator, so this is bounded by the number of registers
+pub const BATCH_BLOCK: usize = 4;
+
+pub(crate) fn check_dimensions(
+    inputs: &[f32],
+    n_rows: usize,
+    outputs: &[f32],
+    num_inputs: usize,
+    num_outputs: usize,
+) -> Result<(), EstimatorError> {
+    // a batch whose lengths overflow can't match any slice
+    let expected_inputs = n_rows
+        .checked_mul(num_inputs)
+        .ok_or(EstimatorError::InputLength {
+            expected: usize::MAX,
+            actual: inputs.len(),
+        })?;
+    let expected_outputs = n_rows
+        .checked_mul(num_outputs)
+        .ok_or(EstimatorError::OutputLength {
+            expected: usize::MAX,
+            actual: outputs.len(),
+        })?;
+    check_lengths(
+        inputs.len(),
+        outputs.len(),
+        expected_inputs,
+        expected_outputs,
+    )
+}
@@ -0,0 +1,162 @@
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx::exp_approx_f32;
+use crate::matrix_dispatch::MatrixF32;
+
+// how the members' outputs are combined into probabilities
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Averaging {
+    // the weighted average of each member's softmax, as for a bagged ensemble
+    Probabilities,
+    // the softmax of the weighted average of the members' linear outputs
+    Logits,
+}
+
+// several linear models of the same shape, e.g. the members of a bagged ensemble,
+// scored together and their outputs averaged with a weight for each. Each member is
+// scored into a scratch buffer kept here, so scoring doesn't allocate, but it does
+// take `&mut self`: use one per thread.
+pub struct Ensemble {
+    members: Vec<MatrixF32>,
+    // normalised to sum to one
+    weights: Vec<f32>,
+    scratch: Vec<f32>,
+}
+
+impl Ensemble {
+    // all the members weighted equally
+    pub fn create(members: Vec<MatrixF32>) -> Result<Self, EstimatorError> {
+        let weights = vec![1f32; members.len()];
+        Self::create_weighted(members, &weights)
+    }
+
+    // one weight per member; they're normalised to sum to one, so only their
+    // proportions matter, but they must be finite, not negative, and not all zero
+    pub fn create_weighted(
+        members: Vec<MatrixF32>,
+        weights: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let first = members.first().ok_or(EstimatorError::NoMembers)?;
+        let expected = (first.num_rows, first.num_columns);
+        for (member, m) in members.iter().enumerate() {
+            let actual = (m.num_rows, m.num_columns);
+            if actual != expected {
+                return Err(EstimatorError::MemberShape {
+                    member,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if weights.len() != members.len() {
+            return Err(EstimatorError::WeightLength {
+                expected: members.len(),
+                actual: weights.len(),
+            });
+        }
+        let total: f32 = weights.iter().sum();
+        if weights.iter().any(|w| !w.is_finite() || *w < 0f32) || total <= 0f32 {
+            return Err(EstimatorError::InvalidWeights);
+        }
+
+        let scratch = vec![0f32; first.num_columns];
+        Ok(Self {
+            weights: weights.iter().map(|w| w / total).collect(),
+            members,
+            scratch,
+        })
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.members[0].num_rows
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.members[0].num_columns
+    }
+
+    pub fn members(&self) -> &[MatrixF32] {
+        &self.members
+    }
+
+    // normalised, as above
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    // the weighted average of the members' linear outputs (the logits)
+    pub fn product(
+        &mut self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.average(values, destination, false)
+    }
+
+    // the probability of each output, normalised to sum to one (using the same
+    // approximate exponential as the softmax elsewhere)
+    pub fn probabilities(
+        &mut self,
+        values: &[f32],
+        destination: &mut [f32],
+        averaging: Averaging,
+    ) -> Result<(), EstimatorError> {
+        match averaging {
+            Averaging::Probabilities => self.average(values, destination, true),
+            Averaging::Logits => {
+                self.average(values, destination, false)?;
+                softmax(destination);
+                Ok(())
+            }
+        }
+    }
+
+    // the weighted average of the members' outputs, each one's softmax first for
+    // the probabilities
+    fn average(
+        &mut self,
+        values: &[f32],
+        destination: &mut [f32],
+        probabilities: bool,
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_inputs(),
+            self.num_outputs(),
+        )?;
+        destination.iter_mut().for_each(|d| *d = 0f32);
+        for (member, weight) in self.members.iter().zip(&self.weights) {
+            member.product(values, &mut self.scratch)?;
+            if probabilities {
+                softmax(&mut self.scratch);
+            }
+            for (d, s) in destination.iter_mut().zip(&self.scratch) {
+                *d += weight * s;
+            }
+        }
+        Ok(())
+    }
+}
+
+// normalised softmax in place; the largest value is taken off first, so large
+// logits don't saturate the exponential (which is clamped at about e^88). Used for
+// both kinds of averaging, so they agree. The max and the sum are taken over
+// `LANES` partial results, so those loops vectorise too.
+fn softmax(values: &mut [f32]) {
+    const LANES: usize = 8;
+    let mut max = [f32::NEG_INFINITY; LANES];
+    for chunk in values.chunks(LANES) {
+        max.iter_mut().zip(chunk).for_each(|(m, v)| *m = m.max(*v));
+    }
+    let max = max.iter().fold(f32::NEG_INFINITY, |m, v| m.max(*v));
+
+    values
+        .iter_mut()
+        .for_each(|v| *v = exp_approx_f32(*v - max));
+    let mut sum = [0f32; LANES];
+    for chunk in values.chunks(LANES) {
+        sum.iter_mut().zip(chunk).for_each(|(s, v)| *s += v);
+    }
+    let scale = 1f32 / sum.iter().sum::<f32>();
+    values.iter_mut().for_each(|v| *v *= scale);
+}
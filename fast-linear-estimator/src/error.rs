@@ -0,0 +1,288 @@
+use std::fmt;
+
+use crate::matrix_dispatch::Backend;
+
+// why creating a matrix, or running it, failed; the dimensions involved are
+// included so the caller can tell what was wrong
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EstimatorError {
+    // no input rows to create a matrix from
+    NoInputs,
+    // input `row` has a different number of coefficients from the first
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        actual: usize,
+    },
+    // a flat coefficient buffer doesn't match its dimensions, or a replacement row
+    // isn't one coefficient per output
+    CoefficientLength {
+        expected: usize,
+        actual: usize,
+    },
+    // coefficients of the wrong (inputs, outputs) shape for a fixed size matrix
+    Shape {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    // the number of intercepts doesn't match the number of outputs
+    InterceptLength {
+        expected: usize,
+        actual: usize,
+    },
+    // the input values (or batch of them) passed to a product
+    InputLength {
+        expected: usize,
+        actual: usize,
+    },
+    // the destination (or batch of them) passed to a product
+    OutputLength {
+        expected: usize,
+        actual: usize,
+    },
+    // an input index, e.g. for sparse inputs or an in-place update
+    InputIndex {
+        index: usize,
+        num_inputs: usize,
+    },
+    // an output index
+    OutputIndex {
+        index: usize,
+        num_outputs: usize,
+    },
+    // sparse inputs (or coefficients) with a different number of indices and values
+    SparseLength {
+        indices: usize,
+        values: usize,
+    },
+    // compressed sparse row offsets that don't fit the inputs and values
+    InvalidOffsets,
+    // the CPU doesn't support this backend, or it has no implementation of this
+    // kind of matrix
+    Unsupported(Backend),
+    // a NaN or infinite coefficient, when validating
+    NonFiniteCoefficient {
+        input: usize,
+        output: usize,
+    },
+    // likewise for an intercept
+    NonFiniteIntercept {
+        output: usize,
+    },
+    // the indices of every NaN or infinite input value, from a checked product
+    NonFiniteInputs(Vec<usize>),
+    // standardisation means or scales that aren't one per input
+    StandardisationLength {
+        expected: usize,
+        actual: usize,
+    },
+    // a standardisation scale that's zero or not finite, so can't be divided by
+    InvalidScale {
+        input: usize,
+    },
+    // a standardisation mean that's not finite
+    InvalidMean {
+        input: usize,
+    },
+    // a network layer whose inputs don't match the outputs of the layer before
+    LayerShape {
+        layer: usize,
+        expected: usize,
+        actual: usize,
+    },
+    // an ensemble with no members
+    NoMembers,
+    // an ensemble member with different (inputs, outputs) from the first
+    MemberShape {
+        member: usize,
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    // ensemble weights that aren't one per member
+    WeightLength {
+        expected: usize,
+        actual: usize,
+    },
+    // ensemble weights that are negative or not finite, or all zero
+    InvalidWeights,
+}
+
+impl fmt::Display for EstimatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EstimatorError::NoInputs => write!(f, "no input rows"),
+            EstimatorError::RaggedRow {
+                row,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "row {} has {} coefficients, but the first row has {}",
+                row, actual, expected
+            ),
+            EstimatorError::CoefficientLength { expected, actual } => {
+                write!(f, "expected {} coefficients, but got {}", expected, actual)
+            }
+            EstimatorError::Shape { expected, actual } => write!(
+                f,
+                "expected {} inputs x {} outputs, but got {} x {}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            EstimatorError::InterceptLength { expected, actual } => write!(
+                f,
+                "expected {} intercepts, one per output, but got {}",
+                expected, actual
+            ),
+            EstimatorError::InputLength { expected, actual } => {
+                write!(f, "expected {} input values, but got {}", expected, actual)
+            }
+            EstimatorError::OutputLength { expected, actual } => write!(
+                f,
+                "expected a destination of {} values, but got {}",
+                expected, actual
+            ),
+            EstimatorError::InputIndex { index, num_inputs } => write!(
+                f,
+                "input index {} is out of range for {} inputs",
+                index, num_inputs
+            ),
+            EstimatorError::OutputIndex { index, num_outputs } => write!(
+                f,
+                "output index {} is out of range for {} outputs",
+                index, num_outputs
+            ),
+            EstimatorError::SparseLength { indices, values } => {
+                write!(f, "{} sparse indices, but {} values", indices, values)
+            }
+            EstimatorError::InvalidOffsets => write!(f, "invalid compressed sparse row offsets"),
+            EstimatorError::NonFiniteCoefficient { input, output } => write!(
+                f,
+                "the coefficient for input {} and output {} is not finite",
+                input, output
+            ),
+            EstimatorError::NonFiniteIntercept { output } => {
+                write!(f, "the intercept for output {} is not finite", output)
+            }
+            EstimatorError::NonFiniteInputs(indices) => {
+                write!(f, "input values at {:?} are not finite", indices)
+            }
+            EstimatorError::StandardisationLength { expected, actual } => write!(
+                f,
+                "expected {} standardisation values, one per input, but got {}",
+                expected, actual
+            ),
+            EstimatorError::InvalidScale { input } => write!(
+                f,
+                "the standardisation scale for input {} is zero or not finite",
+                input
+            ),
+            EstimatorError::InvalidMean { input } => write!(
+                f,
+                "the standardisation mean for input {} is not finite",
+                input
+            ),
+            EstimatorError::LayerShape {
+                layer,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "layer {} has {} inputs, but the layer before has {} outputs",
+                layer, actual, expected
+            ),
+            EstimatorError::NoMembers => write!(f, "no ensemble members"),
+            EstimatorError::MemberShape {
+                member,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "member {} has {} inputs x {} outputs, but the first has {} x {}",
+                member, actual.0, actual.1, expected.0, expected.1
+            ),
+            EstimatorError::WeightLength { expected, actual } => write!(
+                f,
+                "expected {} weights, one per member, but got {}",
+                expected, actual
+            ),
+            EstimatorError::InvalidWeights => write!(
+                f,
+                "weights must be finite and not negative, and not all zero"
+            ),
+            EstimatorError::Unsupported(backend) => {
+                write!(
+                    f,
+                    "{:?} is not supported for this matrix on this machine",
+                    backend
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EstimatorError {}
+
+// the common checks on a product's arguments
+pub(crate) fn check_lengths(
+    values: usize,
+    destination: usize,
+    num_inputs: usize,
+    num_outputs: usize,
+) -> Result<(), EstimatorError> {
+    if values != num_inputs {
+        return Err(EstimatorError::InputLength {
+            expected: num_inputs,
+            actual: values,
+        });
+    }
+    if destination != num_outputs {
+        return Err(EstimatorError::OutputLength {
+            expected: num_outputs,
+            actual: destination,
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_intercepts(
+    intercepts: usize,
+    num_outputs: usize,
+) -> Result<(), EstimatorError> {
+    if intercepts != num_outputs {
+        return Err(EstimatorError::InterceptLength {
+            expected: num_outputs,
+            actual: intercepts,
+        });
+    }
+    Ok(())
+}
+
+// every non-finite value in the inputs to a checked product
+pub(crate) fn check_finite_inputs(values: &[f32]) -> Result<(), EstimatorError> {
+    let indices: Vec<usize> = values
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| !v.is_finite())
+        .map(|(i, _)| i)
+        .collect();
+    if !indices.is_empty() {
+        return Err(EstimatorError::NonFiniteInputs(indices));
+    }
+    Ok(())
+}
+
+// (number of rows, length of each) for a list of rows, checking they're all the
+// same length
+pub(crate) fn row_dimensions<R: AsRef<[E]>, E>(
+    rows: &[R],
+) -> Result<(usize, usize), EstimatorError> {
+    let expected = rows.first().ok_or(EstimatorError::NoInputs)?.as_ref().len();
+    match rows.iter().position(|r| r.as_ref().len() != expected) {
+        Some(row) => Err(EstimatorError::RaggedRow {
+            row,
+            expected,
+            actual: rows[row].as_ref().len(),
+        }),
+        None => Ok((rows.len(), expected)),
+    }
+}
@@ -0,0 +1,374 @@
+use std::convert::TryInto;
+
+use crate::error::{check_finite_inputs, check_lengths, EstimatorError};
+#[cfg(target_arch = "aarch64")]
+use crate::matrix_arm;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx512;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_sse;
+use crate::{batch, matrix_dispatch, matrix_fixed, matrix_scalar, matrix_sparse, sparse};
+
+// common interface over the matrix implementations, so calling code can be written
+// once regardless of which one is in use. Inputs are rows, outputs are columns.
+pub trait LinearEstimator: Send + Sync {
+    fn num_inputs(&self) -> usize;
+    fn num_outputs(&self) -> usize;
+
+    // y = x * [coeff] + [intercepts]
+    fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError>;
+
+    // cumulative sum of the approximate exponential of the above (not normalised)
+    fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError>;
+
+    // normalised softmax probabilities; derived from the cumulative output, so
+    // every implementation gets it for free
+    fn product_softmax_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.product_softmax_cumulative_approx(values, destination)?;
+        // no outputs: nothing to normalise
+        let total = match destination.last() {
+            Some(total) => *total,
+            None => return Ok(()),
+        };
+        let mut previous = 0f32;
+        for d in destination.iter_mut() {
+            let cumulative = *d;
+            *d = (cumulative - previous) / total;
+            previous = cumulative;
+        }
+        Ok(())
+    }
+
+    // as `product` and `product_softmax_cumulative_approx`, but first checks the
+    // inputs are all finite, and reports the ones that aren't; otherwise a NaN input
+    // turns into garbage (not NaN) in the approximate exponential
+    fn product_checked(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_finite_inputs(values)?;
+        self.product(values, destination)
+    }
+
+    fn softmax_cumulative_checked(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_finite_inputs(values)?;
+        self.product_softmax_cumulative_approx(values, destination)
+    }
+
+    // many input vectors in one call: `inputs` holds `n_rows` input vectors one
+    // after the other, and `outputs` receives `n_rows` output vectors the same way.
+    // The default runs them one at a time; the SIMD implementations block over
+    // several inputs per pass.
+    fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        let (num_inputs, num_outputs) = (self.num_inputs(), self.num_outputs());
+        batch::check_dimensions(inputs, n_rows, outputs, num_inputs, num_outputs)?;
+        for (inp, out) in inputs
+            .chunks_exact(num_inputs.max(1))
+            .zip(outputs.chunks_exact_mut(num_outputs.max(1)))
+        {
+            self.product(inp, out)?;
+        }
+        Ok(())
+    }
+
+    fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        let (num_inputs, num_outputs) = (self.num_inputs(), self.num_outputs());
+        batch::check_dimensions(inputs, n_rows, outputs, num_inputs, num_outputs)?;
+        for (inp, out) in inputs
+            .chunks_exact(num_inputs.max(1))
+            .zip(outputs.chunks_exact_mut(num_outputs.max(1)))
+        {
+            self.product_softmax_cumulative_approx(inp, out)?;
+        }
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`. The default fills in a dense input and runs `product`;
+    // the matrix implementations skip the missing rows instead.
+    fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_inputs(),
+            self.num_outputs(),
+        )?;
+        let mut dense = vec![0f32; self.num_inputs()];
+        for (index, val) in indices.iter().zip(values) {
+            dense[*index as usize] += val;
+        }
+        self.product(&dense, destination)
+    }
+
+    fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_inputs(),
+            self.num_outputs(),
+        )?;
+        let mut dense = vec![0f32; self.num_inputs()];
+        for (index, val) in indices.iter().zip(values) {
+            dense[*index as usize] += val;
+        }
+        self.product_softmax_cumulative_approx(&dense, destination)
+    }
+
+    // the batch functions above, split across threads in chunks of at least
+    // `min_chunk_rows` rows (see `parallel::DEFAULT_MIN_CHUNK_ROWS`)
+    #[cfg(feature = "parallel")]
+    fn par_product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+        min_chunk_rows: usize,
+    ) -> Result<(), EstimatorError> {
+        crate::parallel::par_batch(self, inputs, n_rows, outputs, min_chunk_rows, |e, i, n, o| {
+            e.product_batch(i, n, o)
+        })
+    }
+
+    #[cfg(feature = "parallel")]
+    fn par_softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+        min_chunk_rows: usize,
+    ) -> Result<(), EstimatorError> {
+        crate::parallel::par_batch(self, inputs, n_rows, outputs, min_chunk_rows, |e, i, n, o| {
+            e.softmax_cumulative_batch(i, n, o)
+        })
+    }
+}
+
+// implement the trait by forwarding to the inherent methods of the same name
+macro_rules! impl_linear_estimator {
+    ($t:ty) => {
+        impl LinearEstimator for $t {
+            fn num_inputs(&self) -> usize {
+                self.num_rows
+            }
+
+            fn num_outputs(&self) -> usize {
+                self.num_columns
+            }
+
+            fn product(
+                &self,
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product(self, values, destination)
+            }
+
+            fn product_softmax_cumulative_approx(
+                &self,
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product_softmax_cumulative_approx(self, values, destination)
+            }
+
+            fn product_batch(
+                &self,
+                inputs: &[f32],
+                n_rows: usize,
+                outputs: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product_batch(self, inputs, n_rows, outputs)
+            }
+
+            fn softmax_cumulative_batch(
+                &self,
+                inputs: &[f32],
+                n_rows: usize,
+                outputs: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::softmax_cumulative_batch(self, inputs, n_rows, outputs)
+            }
+
+            fn product_sparse(
+                &self,
+                indices: &[u32],
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product_sparse(self, indices, values, destination)
+            }
+
+            fn softmax_cumulative_sparse(
+                &self,
+                indices: &[u32],
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::softmax_cumulative_sparse(self, indices, values, destination)
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+impl_linear_estimator!(matrix_avx512::MatrixF32);
+#[cfg(target_arch = "x86_64")]
+impl_linear_estimator!(matrix_avx::MatrixF32);
+#[cfg(target_arch = "x86_64")]
+impl_linear_estimator!(matrix_sse::MatrixF32);
+#[cfg(target_arch = "aarch64")]
+impl_linear_estimator!(matrix_arm::MatrixF32);
+impl_linear_estimator!(matrix_scalar::MatrixF32);
+impl_linear_estimator!(matrix_dispatch::MatrixF32);
+
+// for matrices that only have the core operations; the rest are the defaults
+macro_rules! impl_core_estimator {
+    ($t:ty) => {
+        impl LinearEstimator for $t {
+            fn num_inputs(&self) -> usize {
+                self.num_rows
+            }
+
+            fn num_outputs(&self) -> usize {
+                self.num_columns
+            }
+
+            fn product(
+                &self,
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product(self, values, destination)
+            }
+
+            fn product_softmax_cumulative_approx(
+                &self,
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                <$t>::product_softmax_cumulative_approx(self, values, destination)
+            }
+        }
+    };
+}
+
+impl_core_estimator!(matrix_sparse::SparseMatrixF32);
+impl_core_estimator!(matrix_dispatch::MatrixI8);
+impl_core_estimator!(matrix_dispatch::MatrixF16);
+impl_core_estimator!(matrix_dispatch::MatrixBF16);
+
+// the slices are checked, then passed on as arrays
+impl<const IN: usize, const OUT: usize> LinearEstimator for matrix_fixed::FixedMatrixF32<IN, OUT> {
+    fn num_inputs(&self) -> usize {
+        IN
+    }
+
+    fn num_outputs(&self) -> usize {
+        OUT
+    }
+
+    fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(values.len(), destination.len(), IN, OUT)?;
+        // can't fail: the lengths were just checked
+        let (values, destination) = (values.try_into().unwrap(), destination.try_into().unwrap());
+        <matrix_fixed::FixedMatrixF32<IN, OUT>>::product(self, values, destination);
+        Ok(())
+    }
+
+    fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(values.len(), destination.len(), IN, OUT)?;
+        let (values, destination) = (values.try_into().unwrap(), destination.try_into().unwrap());
+        <matrix_fixed::FixedMatrixF32<IN, OUT>>::product_softmax_cumulative_approx(
+            self,
+            values,
+            destination,
+        );
+        Ok(())
+    }
+}
+
+// the best estimator for this machine
+pub fn default_estimator(
+    rows: &[Vec<f32>],
+    intercepts: &[f32],
+) -> Result<Box<dyn LinearEstimator>, EstimatorError> {
+    let matrix = matrix_dispatch::MatrixF32::create_from_rows(rows, intercepts)?;
+    Ok(Box::new(matrix))
+}
+
+// every implementation supported on this machine, with the same coefficients;
+// mostly useful for testing and benchmarking them side by side
+pub fn available_estimators(
+    rows: &[Vec<f32>],
+    intercepts: &[f32],
+) -> Vec<Box<dyn LinearEstimator>> {
+    let mut estimators: Vec<Box<dyn LinearEstimator>> = Vec::new();
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if let Ok(m) = matrix_avx512::MatrixF32::create_from_rows(rows, intercepts) {
+            estimators.push(Box::new(m));
+        }
+        if let Ok(m) = matrix_avx::MatrixF32::create_from_rows(rows, intercepts) {
+            estimators.push(Box::new(m));
+        }
+        if let Ok(m) = matrix_sse::MatrixF32::create_from_rows(rows, intercepts) {
+            estimators.push(Box::new(m));
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if let Ok(m) = matrix_arm::MatrixF32::create_from_rows(rows, intercepts) {
+            estimators.push(Box::new(m));
+        }
+    }
+    if let Ok(m) = matrix_scalar::MatrixF32::create_from_rows(rows, intercepts) {
+        estimators.push(Box::new(m));
+    }
+    if let Ok(m) = matrix_sparse::SparseMatrixF32::create_from_rows(rows, intercepts) {
+        estimators.push(Box::new(m));
+    }
+
+    estimators
+}
@@ -1,5 +1,6 @@
 const EXP_BIAS_32: i32 = 127; // zero point for exponent
 
+#[allow(clippy::excessive_precision)] // constants kept as published
 pub mod exp_f32_const {
     // taken from cephes/avxfun
     pub const EXP_HI: f32 = 88.3762626647949;
@@ -25,7 +26,7 @@ pub fn exp_approx_f32(x_in: f32) -> f32 {
     x = x.max(exp_f32_const::EXP_LO);
 
     // apply approximation
-    x = x * std::f32::consts::LOG2_E;
+    x *= std::f32::consts::LOG2_E;
     let fl = x.floor();
     let xf = x - fl;
 
@@ -36,10 +37,68 @@ pub fn exp_approx_f32(x_in: f32) -> f32 {
     x -= kn;
 
     // create integer with bits in the right place, by rounding double to integer,
-    // then re-interpret as a double
-    let xul = ((exp_f32_const::S * x) + exp_f32_const::B) as u32;
-    let res = unsafe { std::mem::transmute::<u32, f32>(xul) };
-    res
+    // then re-interpret as a double. Below zero (the lowest few inputs) gives zero;
+    // with that and the clamp above, the value is in 0..2^31 (at most 2^23 * 254.5),
+    // and never NaN, as `min` and `max` return the other operand. So the conversion
+    // can skip the saturation checks a plain `as` makes, which stop loops of this
+    // from being vectorised.
+    let xul = ((exp_f32_const::S * x) + exp_f32_const::B).max(0f32);
+    // safe: in range of i32, and not NaN, as above
+    f32::from_bits(unsafe { xul.to_int_unchecked::<i32>() } as u32)
+}
+
+const EXP_BIAS_64: i64 = 1023; // zero point for exponent
+
+// double precision needs a different approach to the above: converting the full
+// scaled value to a 64-bit integer isn't available in AVX/AVX2, so instead we
+// calculate 2^x = 2^floor(x) * 2^frac(x), putting floor(x) straight into the
+// exponent bits, and approximating 2^frac(x) with a polynomial on [0, 1)
+#[allow(clippy::excessive_precision)] // constants kept as fitted
+pub mod exp_f64_const {
+    // just inside ln of the largest and smallest normal doubles, so that
+    // floor(x * log2(e)) stays within the exponent range
+    pub const EXP_HI: f64 = 709.78;
+    pub const EXP_LO: f64 = -708.39;
+
+    // 7th order polynomial for 2^x on [0, 1), interpolated at Chebyshev nodes;
+    // max relative error about 5.5e-11
+    pub const D0: f64 = 9.99999999944938933e-01;
+    pub const D1: f64 = 6.93147187603654236e-01;
+    pub const D2: f64 = 2.40226359480826684e-01;
+    pub const D3: f64 = 5.55052816833936369e-02;
+    pub const D4: f64 = 9.61356520779922327e-03;
+    pub const D5: f64 = 1.34293776406189036e-03;
+    pub const D6: f64 = 1.43025554756661256e-04;
+    pub const D7: f64 = 2.16427011005121463e-05;
+
+    pub const BIAS: i64 = super::EXP_BIAS_64;
+}
+
+#[allow(dead_code)]
+pub fn exp_approx_f64(x_in: f64) -> f64 {
+    // clamp x
+    let mut x = x_in;
+    x = x.min(exp_f64_const::EXP_HI);
+    x = x.max(exp_f64_const::EXP_LO);
+
+    // split into integer and fractional parts of the base 2 exponent
+    x *= std::f64::consts::LOG2_E;
+    let fl = x.floor();
+    let xf = x - fl;
+
+    // 2^xf
+    let mut p = exp_f64_const::D7;
+    p = xf * p + exp_f64_const::D6;
+    p = xf * p + exp_f64_const::D5;
+    p = xf * p + exp_f64_const::D4;
+    p = xf * p + exp_f64_const::D3;
+    p = xf * p + exp_f64_const::D2;
+    p = xf * p + exp_f64_const::D1;
+    p = xf * p + exp_f64_const::D0;
+
+    // 2^fl, by putting the integer straight into the exponent bits
+    let scale = f64::from_bits(((fl as i64 + exp_f64_const::BIAS) as u64) << 52);
+    scale * p
 }
 
 #[cfg(test)]
@@ -66,18 +125,109 @@ mod tests {
         check_assert(&expect, &res);
     }
 
+    // the unchecked conversion gives the same bits as a saturating `as u32` would,
+    // through the clamp limits and beyond them, and for NaN
+    #[test]
+    fn exp_approx_f32_limits() {
+        use super::exp_f32_const::*;
+
+        let saturating = |x_in: f32| {
+            // not `clamp`, which keeps NaN
+            let x = x_in.min(EXP_HI);
+            let x = x.max(EXP_LO) * std::f32::consts::LOG2_E;
+            let xf = x - x.floor();
+            let kn = ((C3 * xf + C2) * xf + C1) * xf + C0;
+            f32::from_bits((S * (x - kn) + B) as u32)
+        };
+        let special = [
+            EXP_HI,
+            EXP_LO,
+            EXP_HI + 1.,
+            EXP_LO - 1.,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MAX,
+            f32::MIN,
+            f32::NAN,
+        ];
+        let sweep = (-10000..=10000).map(|i| i as f32 * 0.01);
+        for x in special.iter().copied().chain(sweep) {
+            assert_eq!(
+                super::exp_approx_f32(x).to_bits(),
+                saturating(x).to_bits(),
+                "{}",
+                x
+            );
+        }
+
+        // the top is finite, the bottom is zero, and NaN is taken as the top
+        let top = super::exp_approx_f32(EXP_HI);
+        assert!(top.is_finite());
+        assert_relative_eq!(top, EXP_HI.exp(), max_relative = 1e-4);
+        assert_eq!(super::exp_approx_f32(EXP_LO), 0f32);
+        assert_eq!(super::exp_approx_f32(f32::NAN), top);
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn exp_approx_avxf32() {
         use std::arch::x86_64::*;
 
+        if !is_x86_feature_detected!("avx") {
+            return;
+        }
+
         let input: __m256 = unsafe { _mm256_loadu_ps(&VALS[0]) };
-        let res = crate::exp_approx_avx::exp_approx_avxf32(input);
+        let res = unsafe { crate::exp_approx_avx::exp_approx_avxf32(input) };
 
         let res_f32:[f32;8] = unsafe{ std::mem::transmute(res) };
         check_assert(&expected(), &res_f32);
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn exp_approx_ssef32() {
+        use std::arch::x86_64::*;
+
+        if !is_x86_feature_detected!("sse4.1") {
+            return;
+        }
+
+        unsafe {
+            let x1 = _mm_loadu_ps(&VALS[0]);
+            let x2 = _mm_loadu_ps(&VALS[4]);
+
+            let res1: [f32; 4] = std::mem::transmute(crate::exp_approx_sse::exp_approx_ssef32(x1));
+            let res2: [f32; 4] = std::mem::transmute(crate::exp_approx_sse::exp_approx_ssef32(x2));
+
+            let expect = expected();
+            check_assert(&expect[0..4], &res1);
+            check_assert(&expect[4..8], &res2);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn exp_approx_avx512f32() {
+        use std::arch::x86_64::*;
+
+        if !is_x86_feature_detected!("avx512f") {
+            return;
+        }
+
+        // second half is the same values negated, to use all 16 lanes
+        let mut vals = [0f32; 16];
+        vals[..8].copy_from_slice(&VALS);
+        vals[8..].iter_mut().zip(&VALS).for_each(|(v, x)| *v = -x);
+
+        let input: __m512 = unsafe { _mm512_loadu_ps(&vals[0]) };
+        let res = unsafe { crate::exp_approx_avx512::exp_approx_avx512f32(input) };
+
+        let res_f32: [f32; 16] = unsafe { std::mem::transmute(res) };
+        let expect: Vec<f32> = vals.iter().map(|v| v.exp()).collect();
+        check_assert(&expect, &res_f32);
+    }
+
     #[cfg(target_arch = "aarch64")]
     #[test]
     fn exp_approx_armf32() {
@@ -95,4 +245,57 @@ mod tests {
             check_assert(&expect[4..8], &res2);
         }
     }
+
+    const VALS_F64: [f64; 8] = [-100_f64, -10., -1., 0., 1., 2., 10., 100.];
+
+    fn check_assert_f64(vals: &[f64], res: &[f64]) {
+        vals.iter().zip(res.iter()).for_each(|(v, act)| {
+            assert_relative_eq!(v.exp(), act, max_relative = 1e-9);
+        });
+    }
+
+    #[test]
+    fn exp_approx_f64() {
+        let res: Vec<_> = VALS_F64.iter().map(|&v| super::exp_approx_f64(v)).collect();
+        check_assert_f64(&VALS_F64, &res);
+
+        // clamped at the ends of the range rather than overflowing
+        assert!(super::exp_approx_f64(1e6).is_finite());
+        assert!(super::exp_approx_f64(-1e6) > 0.0);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn exp_approx_avxf64() {
+        use std::arch::x86_64::*;
+
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        unsafe {
+            let x1 = _mm256_loadu_pd(&VALS_F64[0]);
+            let x2 = _mm256_loadu_pd(&VALS_F64[4]);
+
+            let res1: [f64; 4] = std::mem::transmute(crate::exp_approx_avx::exp_approx_avxf64(x1));
+            let res2: [f64; 4] = std::mem::transmute(crate::exp_approx_avx::exp_approx_avxf64(x2));
+
+            check_assert_f64(&VALS_F64[0..4], &res1);
+            check_assert_f64(&VALS_F64[4..8], &res2);
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn exp_approx_armf64() {
+        use std::arch::aarch64::*;
+
+        unsafe {
+            for vals in VALS_F64.chunks(2) {
+                let x: float64x2_t = std::mem::transmute([vals[0], vals[1]]);
+                let res: [f64; 2] = std::mem::transmute(crate::exp_approx_arm::exp_approx_armf64(x));
+                check_assert_f64(vals, &res);
+            }
+        }
+    }
 }
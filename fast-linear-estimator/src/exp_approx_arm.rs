@@ -1,6 +1,7 @@
 use std::arch::aarch64::*;
+use std::arch::asm;
 
-use crate::exp_approx::exp_f32_const;
+use crate::exp_approx::{exp_f32_const, exp_f64_const};
 
 #[allow(dead_code)]
 #[inline(always)]
@@ -81,3 +82,35 @@ pub fn exp_approx_armf32(x_in: float32x4_t) -> float32x4_t {
     }
 }
 
+// double precision; see `exp_approx::exp_approx_f64` for the approach. The
+// float64x2_t intrinsics we need are all available, so no assembly here.
+#[allow(dead_code)]
+#[inline(always)]
+pub fn exp_approx_armf64(x_in: float64x2_t) -> float64x2_t {
+    unsafe {
+        // clamp x
+        let mut x = vminq_f64(x_in, vdupq_n_f64(exp_f64_const::EXP_HI));
+        x = vmaxq_f64(x, vdupq_n_f64(exp_f64_const::EXP_LO));
+
+        // split into integer and fractional parts of the base 2 exponent
+        x = vmulq_n_f64(x, std::f64::consts::LOG2_E);
+        let fl = vrndmq_f64(x); // floor
+        let xf = vsubq_f64(x, fl);
+
+        // 2^xf, using fused multiply-add: vfmaq_f64(a, b, c) = a + b * c
+        let mut p = vdupq_n_f64(exp_f64_const::D7);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D6), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D5), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D4), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D3), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D2), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D1), xf, p);
+        p = vfmaq_f64(vdupq_n_f64(exp_f64_const::D0), xf, p);
+
+        // 2^fl, by putting the integer straight into the exponent bits
+        let biased = vaddq_s64(vcvtq_s64_f64(fl), vdupq_n_s64(exp_f64_const::BIAS));
+        let scale = vreinterpretq_f64_s64(vshlq_n_s64::<52>(biased));
+
+        vmulq_f64(scale, p)
+    }
+}
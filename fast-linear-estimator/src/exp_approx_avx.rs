@@ -1,37 +1,75 @@
 use std::arch::x86_64::*;
 
-use crate::exp_approx::exp_f32_const;
+use crate::exp_approx::{exp_f32_const, exp_f64_const};
 
+/// # Safety
+/// Requires AVX; check with `is_x86_feature_detected!("avx")` before calling.
 #[allow(dead_code)]
-pub fn exp_approx_avxf32(x_in: __m256) -> __m256 {
+#[inline]
+#[target_feature(enable = "avx")]
+pub unsafe fn exp_approx_avxf32(x_in: __m256) -> __m256 {
     let mut x = x_in;
-    unsafe {
-        // clamp x
-        x = _mm256_min_ps(x, _mm256_set1_ps(exp_f32_const::EXP_HI));
-        x = _mm256_max_ps(x, _mm256_set1_ps(exp_f32_const::EXP_LO_AVX_SIGNED));
-
-        // apply approximation
-        x = _mm256_mul_ps(x, _mm256_set1_ps(std::f32::consts::LOG2_E));
-        let fl = _mm256_floor_ps(x);
-        let xf = _mm256_sub_ps(x, fl);
-
-        let mut kn = _mm256_set1_ps(exp_f32_const::C3);
-        // multiply add (no benefit from using FMA here, unfortunately)
-        kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C2));
-        kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C1));
-        kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C0));
-        x = _mm256_sub_ps(x, kn);
-
-        // create integer with bits in the right place, by rounding double to integer,
-        // then re-interpret as a double; again no benefit from using FMA here
-        let xf32 = _mm256_add_ps(
-            _mm256_mul_ps(_mm256_set1_ps(exp_f32_const::S), x),
-            _mm256_set1_ps(exp_f32_const::B),
-        );
-        let xul = _mm256_cvtps_epi32(xf32); // convert (numerically) to i32
-        let res = _mm256_castsi256_ps(xul); // now cast back to f32
-
-        res
-    }
+
+    // clamp x
+    x = _mm256_min_ps(x, _mm256_set1_ps(exp_f32_const::EXP_HI));
+    x = _mm256_max_ps(x, _mm256_set1_ps(exp_f32_const::EXP_LO_AVX_SIGNED));
+
+    // apply approximation
+    x = _mm256_mul_ps(x, _mm256_set1_ps(std::f32::consts::LOG2_E));
+    let fl = _mm256_floor_ps(x);
+    let xf = _mm256_sub_ps(x, fl);
+
+    let mut kn = _mm256_set1_ps(exp_f32_const::C3);
+    // multiply add (no benefit from using FMA here, unfortunately)
+    kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C2));
+    kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C1));
+    kn = _mm256_add_ps(_mm256_mul_ps(xf, kn), _mm256_set1_ps(exp_f32_const::C0));
+    x = _mm256_sub_ps(x, kn);
+
+    // create integer with bits in the right place, by rounding double to integer,
+    // then re-interpret as a double; again no benefit from using FMA here
+    let xf32 = _mm256_add_ps(
+        _mm256_mul_ps(_mm256_set1_ps(exp_f32_const::S), x),
+        _mm256_set1_ps(exp_f32_const::B),
+    );
+    let xul = _mm256_cvtps_epi32(xf32); // convert (numerically) to i32
+    _mm256_castsi256_ps(xul) // now cast back to f32
 }
 
+// double precision; see `exp_approx::exp_approx_f64` for the approach. Needs AVX2
+// for the 64-bit integer operations used to build the exponent.
+//
+/// # Safety
+/// Requires AVX2; check with `is_x86_feature_detected!("avx2")` before calling.
+#[allow(dead_code)]
+#[inline]
+#[target_feature(enable = "avx2")]
+pub unsafe fn exp_approx_avxf64(x_in: __m256d) -> __m256d {
+    let mut x = x_in;
+
+    // clamp x
+    x = _mm256_min_pd(x, _mm256_set1_pd(exp_f64_const::EXP_HI));
+    x = _mm256_max_pd(x, _mm256_set1_pd(exp_f64_const::EXP_LO));
+
+    // split into integer and fractional parts of the base 2 exponent
+    x = _mm256_mul_pd(x, _mm256_set1_pd(std::f64::consts::LOG2_E));
+    let fl = _mm256_floor_pd(x);
+    let xf = _mm256_sub_pd(x, fl);
+
+    // 2^xf
+    let mut p = _mm256_set1_pd(exp_f64_const::D7);
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D6));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D5));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D4));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D3));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D2));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D1));
+    p = _mm256_add_pd(_mm256_mul_pd(xf, p), _mm256_set1_pd(exp_f64_const::D0));
+
+    // 2^fl, by putting the integer straight into the exponent bits
+    let fl_i64 = _mm256_cvtepi32_epi64(_mm256_cvtpd_epi32(fl));
+    let biased = _mm256_add_epi64(fl_i64, _mm256_set1_epi64x(exp_f64_const::BIAS));
+    let scale = _mm256_castsi256_pd(_mm256_slli_epi64::<52>(biased));
+
+    _mm256_mul_pd(scale, p)
+}
@@ -0,0 +1,38 @@
+use std::arch::x86_64::*;
+
+use crate::exp_approx::exp_f32_const;
+
+// 16-lane version of `exp_approx_avxf32`; same constants and algorithm
+//
+/// # Safety
+/// Requires AVX-512F; check with `is_x86_feature_detected!("avx512f")` before calling.
+#[allow(dead_code)]
+#[inline]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn exp_approx_avx512f32(x_in: __m512) -> __m512 {
+    let mut x = x_in;
+
+    // clamp x
+    x = _mm512_min_ps(x, _mm512_set1_ps(exp_f32_const::EXP_HI));
+    x = _mm512_max_ps(x, _mm512_set1_ps(exp_f32_const::EXP_LO_AVX_SIGNED));
+
+    // apply approximation
+    x = _mm512_mul_ps(x, _mm512_set1_ps(std::f32::consts::LOG2_E));
+    let fl = _mm512_roundscale_ps::<_MM_FROUND_TO_NEG_INF>(x); // floor
+    let xf = _mm512_sub_ps(x, fl);
+
+    let mut kn = _mm512_set1_ps(exp_f32_const::C3);
+    kn = _mm512_add_ps(_mm512_mul_ps(xf, kn), _mm512_set1_ps(exp_f32_const::C2));
+    kn = _mm512_add_ps(_mm512_mul_ps(xf, kn), _mm512_set1_ps(exp_f32_const::C1));
+    kn = _mm512_add_ps(_mm512_mul_ps(xf, kn), _mm512_set1_ps(exp_f32_const::C0));
+    x = _mm512_sub_ps(x, kn);
+
+    // create integer with bits in the right place, by rounding double to integer,
+    // then re-interpret as a double
+    let xf32 = _mm512_add_ps(
+        _mm512_mul_ps(_mm512_set1_ps(exp_f32_const::S), x),
+        _mm512_set1_ps(exp_f32_const::B),
+    );
+    let xul = _mm512_cvtps_epi32(xf32); // convert (numerically) to i32
+    _mm512_castsi512_ps(xul) // now cast back to f32
+}
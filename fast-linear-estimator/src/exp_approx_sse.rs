@@ -0,0 +1,38 @@
+use std::arch::x86_64::*;
+
+use crate::exp_approx::exp_f32_const;
+
+// 4-lane version of `exp_approx_avxf32`; same constants and algorithm
+//
+/// # Safety
+/// Requires SSE4.1; check with `is_x86_feature_detected!("sse4.1")` before calling.
+#[allow(dead_code)]
+#[inline]
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn exp_approx_ssef32(x_in: __m128) -> __m128 {
+    let mut x = x_in;
+
+    // clamp x
+    x = _mm_min_ps(x, _mm_set1_ps(exp_f32_const::EXP_HI));
+    x = _mm_max_ps(x, _mm_set1_ps(exp_f32_const::EXP_LO_AVX_SIGNED));
+
+    // apply approximation
+    x = _mm_mul_ps(x, _mm_set1_ps(std::f32::consts::LOG2_E));
+    let fl = _mm_floor_ps(x);
+    let xf = _mm_sub_ps(x, fl);
+
+    let mut kn = _mm_set1_ps(exp_f32_const::C3);
+    kn = _mm_add_ps(_mm_mul_ps(xf, kn), _mm_set1_ps(exp_f32_const::C2));
+    kn = _mm_add_ps(_mm_mul_ps(xf, kn), _mm_set1_ps(exp_f32_const::C1));
+    kn = _mm_add_ps(_mm_mul_ps(xf, kn), _mm_set1_ps(exp_f32_const::C0));
+    x = _mm_sub_ps(x, kn);
+
+    // create integer with bits in the right place, by rounding double to integer,
+    // then re-interpret as a double
+    let xf32 = _mm_add_ps(
+        _mm_mul_ps(_mm_set1_ps(exp_f32_const::S), x),
+        _mm_set1_ps(exp_f32_const::B),
+    );
+    let xul = _mm_cvtps_epi32(xf32); // convert (numerically) to i32
+    _mm_castsi128_ps(xul) // now cast back to f32
+}
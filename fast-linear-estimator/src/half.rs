@@ -0,0 +1,221 @@
+// 16-bit coefficient storage, shared by the `MatrixF16` and `MatrixBF16` implementations
+//
+// the coefficients are kept as the raw bits of either IEEE half precision (f16: 5 bit
+// exponent, 10 bit mantissa) or bfloat16 (bf16: the top half of an f32, so the same
+// range with a 7 bit mantissa), and widened to f32 in the kernels; intercepts and the
+// accumulation stay in f32. The layout is column chunk major as for the other
+// matrices: for each chunk of 8 outputs, the f32 intercepts, and one `[u16; 8]` per
+// input row.
+
+use crate::error::{check_intercepts, row_dimensions, EstimatorError};
+
+pub const HALVES_PER_CHUNK: usize = 8;
+
+pub type HalfLanes = [u16; HALVES_PER_CHUNK];
+pub type Lanes = [f32; HALVES_PER_CHUNK];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HalfFormat {
+    F16,
+    BF16,
+}
+
+// drop the low `shift` bits, rounding to nearest with ties to even; a carry out of
+// the mantissa correctly bumps the exponent
+fn round_shift(v: u32, shift: u32) -> u32 {
+    let remainder = v & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let r = v >> shift;
+    if remainder > halfway || (remainder == halfway && r & 1 == 1) {
+        r + 1
+    } else {
+        r
+    }
+}
+
+fn f32_to_f16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let man = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        // infinity, or a quiet NaN
+        return sign | 0x7c00 | if man != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exp = exp - 127 + 15;
+    let magnitude = if half_exp >= 0x1f {
+        // too large: infinity
+        0x7c00
+    } else if half_exp > 0 {
+        round_shift(((half_exp as u32) << 23) | man, 13)
+    } else if half_exp >= -10 {
+        // subnormal, in units of 2^-24
+        round_shift(man | 0x80_0000, (14 - half_exp) as u32)
+    } else {
+        0
+    };
+    sign | magnitude as u16
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let man = (h & 0x3ff) as u32;
+
+    match exp {
+        0 => {
+            let magnitude = man as f32 / (1 << 24) as f32;
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (man << 13)),
+        _ => f32::from_bits(sign | ((exp + 127 - 15) << 23) | (man << 13)),
+    }
+}
+
+fn f32_to_bf16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    if x.is_nan() {
+        // keep it a NaN even if the payload is all in the low bits
+        return ((bits >> 16) | 0x40) as u16;
+    }
+    let sign = bits & 0x8000_0000;
+    ((sign >> 16) | round_shift(bits & 0x7fff_ffff, 16)) as u16
+}
+
+fn bf16_to_f32(h: u16) -> f32 {
+    f32::from_bits((h as u32) << 16)
+}
+
+impl HalfFormat {
+    pub fn from_f32(self, x: f32) -> u16 {
+        match self {
+            HalfFormat::F16 => f32_to_f16(x),
+            HalfFormat::BF16 => f32_to_bf16(x),
+        }
+    }
+
+    pub fn to_f32(self, h: u16) -> f32 {
+        match self {
+            HalfFormat::F16 => f16_to_f32(h),
+            HalfFormat::BF16 => bf16_to_f32(h),
+        }
+    }
+}
+
+pub struct HalfColumns {
+    pub format: HalfFormat,
+    pub num_columns: usize,
+    pub num_rows: usize,
+    intercepts: Vec<Lanes>,
+    coefficients: Vec<HalfLanes>,
+}
+
+impl HalfColumns {
+    pub fn from_rows(
+        format: HalfFormat,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let (_, num_columns) = row_dimensions(rows)?;
+        check_intercepts(intercepts.len(), num_columns)?;
+
+        let num_chunks = num_columns.div_ceil(HALVES_PER_CHUNK);
+        let mut coefficients = vec![[0u16; HALVES_PER_CHUNK]; num_chunks * rows.len()];
+        for (chunk_num, chunk) in coefficients.chunks_exact_mut(rows.len()).enumerate() {
+            let start = chunk_num * HALVES_PER_CHUNK;
+            let end = (start + HALVES_PER_CHUNK).min(num_columns);
+            for (dest, r) in chunk.iter_mut().zip(rows) {
+                for (d, c) in dest.iter_mut().zip(&r[start..end]) {
+                    *d = format.from_f32(*c);
+                }
+            }
+        }
+
+        Ok(Self {
+            format,
+            num_columns,
+            num_rows: rows.len(),
+            intercepts: intercepts
+                .chunks(HALVES_PER_CHUNK)
+                .map(|c| {
+                    let mut lanes = [0f32; HALVES_PER_CHUNK];
+                    lanes[..c.len()].copy_from_slice(c);
+                    lanes
+                })
+                .collect(),
+            coefficients,
+        })
+    }
+
+    // (intercepts, one `HalfLanes` per row) for each column chunk in turn
+    #[inline(always)]
+    pub fn chunks(&self) -> impl Iterator<Item = (&Lanes, &[HalfLanes])> {
+        self.intercepts
+            .iter()
+            .zip(self.coefficients.chunks_exact(self.num_rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HalfFormat;
+
+    #[test]
+    fn half_conversions() {
+        let f16 = HalfFormat::F16;
+        assert_eq!(f16.from_f32(1.0), 0x3c00);
+        assert_eq!(f16.from_f32(-2.0), 0xc000);
+        assert_eq!(f16.from_f32(65504.0), 0x7bff);
+        // rounds up past the largest finite value
+        assert_eq!(f16.from_f32(65520.0), 0x7c00);
+        assert_eq!(f16.from_f32(2f32.powi(-24)), 0x0001);
+        assert_eq!(f16.from_f32(2f32.powi(-26)), 0x0000);
+        assert_eq!(f16.from_f32(-0.0), 0x8000);
+        // ties go to even
+        assert_eq!(f16.from_f32(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f16.from_f32(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert!(f16.to_f32(f16.from_f32(f32::NAN)).is_nan());
+        assert_eq!(f16.to_f32(f16.from_f32(f32::INFINITY)), f32::INFINITY);
+
+        let bf16 = HalfFormat::BF16;
+        assert_eq!(bf16.from_f32(1.0), 0x3f80);
+        assert_eq!(bf16.from_f32(-1.0), 0xbf80);
+        assert_eq!(bf16.from_f32(1.0 + 2f32.powi(-8)), 0x3f80);
+        assert_eq!(bf16.from_f32(1.0 + 3.0 * 2f32.powi(-8)), 0x3f82);
+        // same range as f32, with about 3 significant digits
+        let large = bf16.to_f32(bf16.from_f32(3e38));
+        assert!(((large - 3e38) / 3e38).abs() < 2f32.powi(-8));
+        assert!(bf16.to_f32(bf16.from_f32(f32::NAN)).is_nan());
+
+        // every f16 survives the round trip through f32
+        for h in (0..=0xffffu16).filter(|h| h & 0x7c00 != 0x7c00) {
+            assert_eq!(f16.from_f32(f16.to_f32(h)), h);
+        }
+    }
+
+    // the conversion should match the hardware one exactly
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn half_conversions_f16c() {
+        use std::arch::x86_64::*;
+
+        if !is_x86_feature_detected!("f16c") {
+            return;
+        }
+
+        let values: Vec<f32> = (0..20000)
+            .map(|i| ((i as f32) * 0.731).sin() * 10f32.powi(i % 14 - 9))
+            .collect();
+        for chunk in values.chunks_exact(4) {
+            let hardware: [u16; 8] = unsafe {
+                let x = _mm_loadu_ps(chunk.as_ptr());
+                std::mem::transmute(_mm_cvtps_ph::<_MM_FROUND_TO_NEAREST_INT>(x))
+            };
+            for (x, h) in chunk.iter().zip(&hardware) {
+                assert_eq!(HalfFormat::F16.from_f32(*x), *h, "{}", x);
+            }
+        }
+    }
+}
@@ -9,17 +9,107 @@ pub mod exp_approx;
 #[cfg(target_arch = "x86_64")]
 pub mod exp_approx_avx;
 
+#[cfg(target_arch = "x86_64")]
+pub mod exp_approx_avx512;
+
+#[cfg(target_arch = "x86_64")]
+pub mod exp_approx_sse;
+
 #[cfg(target_arch = "aarch64")]
 pub mod exp_approx_arm;
 
 //
 // matrix implementation
 //
+
+// what went wrong creating or running a matrix
+pub mod error;
+pub use error::EstimatorError;
+
+pub mod batch;
+pub mod sparse;
+
+// coefficient storage shared by the implementations below
+pub mod packed;
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
+
 #[cfg(target_arch = "x86_64")]
 pub mod matrix_avx;
 
+#[cfg(target_arch = "x86_64")]
+pub mod matrix_avx512;
+
+#[cfg(target_arch = "x86_64")]
+pub mod matrix_sse;
+
 #[cfg(target_arch = "aarch64")]
 pub mod matrix_arm;
 
-pub mod matrix_test;
+// portable fallback; always built so it can be compared against the SIMD versions
+pub mod matrix_scalar;
+
+// double precision versions of the above
+#[cfg(target_arch = "x86_64")]
+pub mod matrix_avx_f64;
+
+#[cfg(target_arch = "aarch64")]
+pub mod matrix_arm_f64;
+
+pub mod matrix_scalar_f64;
+
+// int8 quantized coefficients, with f32 inputs and outputs
+pub mod quantize;
+pub use quantize::QuantizationReport;
 
+// 16-bit (f16 or bf16) coefficients, with f32 inputs and outputs
+pub mod half;
+
+#[cfg(target_arch = "x86_64")]
+pub mod matrix_avx_f16;
+
+#[cfg(target_arch = "aarch64")]
+pub mod matrix_arm_f16;
+
+pub mod matrix_scalar_f16;
+
+#[cfg(target_arch = "x86_64")]
+pub mod matrix_avx_i8;
+
+#[cfg(target_arch = "aarch64")]
+pub mod matrix_arm_i8;
+
+pub mod matrix_scalar_i8;
+
+// for mostly-zero coefficients
+pub mod matrix_sparse;
+pub use matrix_sparse::SparseMatrixF32;
+
+// dimensions fixed at compile time, stored inline
+pub mod matrix_fixed;
+pub use matrix_fixed::FixedMatrixF32;
+
+// picks the best of the above at runtime
+pub mod matrix_dispatch;
+
+// default matrix implementation: dispatches to whatever the CPU supports
+pub use matrix_dispatch::{MatrixBF16, MatrixF16, MatrixF32, MatrixF64, MatrixI8};
+
+// layers of the above, with activations between them
+pub mod mlp;
+pub use mlp::{Activation, Mlp};
+
+// several of the above, averaged
+pub mod ensemble;
+pub use ensemble::{Averaging, Ensemble};
+
+// incremental re-scoring as inputs change
+pub mod scorer;
+pub use scorer::Scorer;
+
+// common interface over all of the above
+pub mod estimator;
+pub use estimator::{default_estimator, LinearEstimator};
+
+pub mod matrix_test;
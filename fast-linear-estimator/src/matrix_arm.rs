@@ -1,6 +1,12 @@
 use std::arch::aarch64::*;
+use std::arch::asm;
 use std::mem::transmute;
 
+use crate::batch;
+use crate::error::{check_lengths, EstimatorError};
+use crate::packed::{self, Coefficients, KernelF32, PackedColumns};
+use crate::sparse;
+
 pub const SINGLES_PER_INTRINSIC: usize = 4;
 
 // matrix of f32, but we split the supplied rows into
@@ -10,130 +16,231 @@ pub struct MatrixF32 {
     pub num_columns: usize,
     pub num_col_instrinsics: usize,
     pub num_rows: usize,
-    column_intrinsics: Vec<Vec<float32x4_t>>,
-    intercept_intrinsics: Vec<float32x4_t>,
+    columns: PackedColumns<float32x4_t>,
 }
 
 pub fn zeros() -> float32x4_t {
     unsafe { std::mem::transmute([0f32; SINGLES_PER_INTRINSIC]) }
 }
 
-impl MatrixF32 {
-    pub fn create_from_rows(rows: &Vec<Vec<f32>>, intercepts: &[f32]) -> Option<Self> {
-        let num_columns = rows.first()?.len();
-        if num_columns != intercepts.len() {
-            return None;
-        }
+// safe: an intrinsic is exactly `SINGLES_PER_INTRINSIC` f32s
+unsafe impl packed::Lanes for float32x4_t {
+    type Elem = f32;
+    const LANES: usize = SINGLES_PER_INTRINSIC;
+}
 
-        let num_col_instrinsics = (num_columns / SINGLES_PER_INTRINSIC)
-            + match num_columns % SINGLES_PER_INTRINSIC {
-                0 => 0,
-                _ => 1,
-            };
-
-        let mut mat = Self {
-            num_columns,
-            num_col_instrinsics,
-            num_rows: rows.len(),
-            column_intrinsics: vec![],
-            intercept_intrinsics: vec![zeros(); num_col_instrinsics],
-        };
+// the intrinsics for the kernels in `packed`; NEON is always there on aarch64, so
+// nothing here needs a target feature
+impl KernelF32 for float32x4_t {
+    // `fmla` takes the value from a lane, so there's no broadcast to share
+    type Value = f32;
 
-        // copy intercepts
-        for (intercept_chunk, dest) in intercepts
-            .chunks(SINGLES_PER_INTRINSIC)
-            .zip(mat.intercept_intrinsics.iter_mut())
-        {
-            let dest_cast: &mut [f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(dest) };
-            dest_cast[..intercept_chunk.len()].copy_from_slice(intercept_chunk);
-        }
+    #[inline(always)]
+    unsafe fn broadcast(value: f32) -> f32 {
+        value
+    }
 
-        // copy coefficients
-        for chunk_num in 0..num_col_instrinsics {
-            let mut col: Vec<float32x4_t> = Vec::new();
-            for r in rows {
-                let chunk = r.chunks(SINGLES_PER_INTRINSIC).nth(chunk_num)?;
-                let mut intrin = [0f32; SINGLES_PER_INTRINSIC];
-                intrin[..chunk.len()].copy_from_slice(chunk);
-                col.push(unsafe { transmute(intrin) });
-            }
-            mat.column_intrinsics.push(col);
-        }
+    #[inline(always)]
+    unsafe fn multiply_add(acc: &mut float32x4_t, row: float32x4_t, value: f32) {
+        // fused multiply-add works a treat on the raspberry pi; a lot faster than separate ops
+        asm!(
+            "fmla   {acc:v}.4s,   {row:v}.4s, {val:v}.s[0]",    // fused multiply-add
+            row = in(vreg) row,                                 // load row value
+            val = in(vreg) value,                               // load into first element of vector
+            acc = inout(vreg) *acc,
+            options(pure,nomem,nostack)
+        );
+    }
 
-        Some(mat)
+    #[inline(always)]
+    unsafe fn exp(v: float32x4_t) -> float32x4_t {
+        crate::exp_approx_arm::exp_approx_armf32(v)
     }
 
+    // a whole intrinsic directly, or the final shorter stub through an array
     #[inline(always)]
-    fn multiply_add(acc: &mut float32x4_t, v1: float32x4_t, v2: f32) {
-        unsafe {
-            // fused multiply-add works a treat on the raspberry pi; a lot faster than separate ops
-            asm!(
-                "fmla   {acc:v}.4s,   {row:v}.4s, {val:v}.s[0]",    // fused multiply-add
-                row = in(vreg) v1,                                  // load row value
-                val = in(vreg) v2,                                  // load into first element of vector
-                acc = inout(vreg) *acc,
-                options(pure,nomem,nostack)
-            );
+    unsafe fn load(src: &[f32]) -> float32x4_t {
+        if src.len() == SINGLES_PER_INTRINSIC {
+            vld1q_f32(src.as_ptr())
+        } else {
+            let mut lanes = [0f32; SINGLES_PER_INTRINSIC];
+            lanes[..src.len()].copy_from_slice(src);
+            vld1q_f32(lanes.as_ptr())
         }
     }
 
-    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Option<()> {
-        if destination.len() != self.num_columns || values.len() != self.num_rows {
-            return None;
-        }
+    // by interpreting the intrinsic as a slice, as we might have a shorter final slice
+    #[inline(always)]
+    unsafe fn store(dst: &mut [f32], v: float32x4_t) {
+        let src: &[f32; SINGLES_PER_INTRINSIC] = transmute(&v);
+        dst.copy_from_slice(&src[..dst.len()]);
+    }
+}
+
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    packed::impl_updates!(f32);
+    packed::impl_read_back!(f32);
 
-        destination
-            .chunks_mut(SINGLES_PER_INTRINSIC)
-            .zip(self.column_intrinsics.iter())
-            .zip(self.intercept_intrinsics.iter())
-            .for_each(|((dst, col), intercepts)| {
-                // run multiplication and add to `accumulate`
-                let mut accumulate = *intercepts;
-                for (val, row_intrin) in values.iter().zip(col) {
-                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
-                }
-                // copy to destination (by interpreting the intrinsic as a slice) -- and we might
-                // have a shorter final slice
-                let src: &[f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
-                dst.copy_from_slice(&src[0..dst.len()]);
-            });
-
-        Some(())
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: NEON needs no target feature check
+        unsafe { self.columns.product(values, destination, false) };
+        Ok(())
     }
 
     pub fn product_softmax_cumulative_approx(
         &self,
         values: &[f32],
         destination: &mut [f32],
-    ) -> Option<()> {
-        if destination.len() != self.num_columns || values.len() != self.num_rows {
-            return None;
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: NEON needs no target feature check
+        unsafe { self.columns.product(values, destination, true) };
+        Ok(())
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            output_indices.len(),
+        )?;
+
+        // safe: NEON needs no target feature check
+        unsafe {
+            self.columns
+                .product_selected(values, output_indices, destination)
         }
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        let mut destination = [0f32];
+        self.product_selected(values, &[output], &mut destination)?;
+        Ok(destination[0])
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one
+    // value per output, as `Scorer` does when an input changes
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        // safe: NEON needs no target feature check
+        unsafe { self.columns.add_scaled_row(input, delta, destination) }
+    }
 
-        let mut cumulative_sum = 0f32;
-
-        destination
-            .chunks_mut(SINGLES_PER_INTRINSIC)
-            .zip(self.column_intrinsics.iter())
-            .zip(self.intercept_intrinsics.iter())
-            .for_each(|((dst, col), intercepts)| {
-                // run multiplication and add to `accumulate`, starting with the intercepts
-                let mut accumulate = *intercepts;
-                for (val, row_intrin) in values.iter().zip(col) {
-                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
-                }
-
-                // copy to destination (taking into account final shorter stub) and apply cumulative softmax
-                // 1. approximate exponential
-                accumulate = crate::exp_approx_arm::exp_approx_armf32(accumulate);
-                // 2. accumulate and copy
-                let src: &[f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
-                dst.iter_mut().zip(src).for_each(|(d, s)| {
-                    cumulative_sum += s;
-                    *d = cumulative_sum;
-                });
-            });
-
-        Some(())
+    // `inputs` holds `n_rows` input vectors one after the other, and `outputs`
+    // receives `n_rows` output vectors in the same way
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: NEON needs no target feature check
+        unsafe { self.columns.product_batch(inputs, outputs, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: NEON needs no target feature check
+        unsafe { self.columns.product_batch(inputs, outputs, true) };
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; everything else is skipped
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: NEON needs no target feature check, and the indices were checked above
+        unsafe {
+            self.columns
+                .product_sparse(indices, values, destination, false)
+        };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: NEON needs no target feature check, and the indices were checked above
+        unsafe {
+            self.columns
+                .product_sparse(indices, values, destination, true)
+        };
+        Ok(())
     }
 }
@@ -0,0 +1,136 @@
+use std::arch::aarch64::*;
+use std::arch::asm;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx_arm::exp_approx_armf32;
+use crate::half::{HalfColumns, HalfFormat, HALVES_PER_CHUNK};
+
+// matrix of 16-bit (f16 or bf16) coefficients, see `half`. Each row of a chunk is
+// 8 halves, which we widen to two NEON intrinsics of f32 before the multiply-add:
+//
+//  * f16 with `fcvtl` / `fcvtl2`; the f16 vector types aren't stable in Rust yet,
+//    so this is inline assembly as for the exponential
+//  * bf16 by shifting each half into the top of 32 bits with `vshll`
+pub struct MatrixHalf {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: HalfColumns,
+}
+
+#[inline(always)]
+fn widen_f16(halves: uint16x8_t) -> [float32x4_t; 2] {
+    let low: float32x4_t;
+    let high: float32x4_t;
+    unsafe {
+        asm!(
+            "fcvtl  {low:v}.4s,  {h:v}.4h",
+            "fcvtl2 {high:v}.4s, {h:v}.8h",
+            h = in(vreg) halves,
+            low = out(vreg) low,
+            high = out(vreg) high,
+            options(nostack, nomem, pure)
+        );
+    }
+    [low, high]
+}
+
+#[inline(always)]
+fn widen_bf16(halves: uint16x8_t) -> [float32x4_t; 2] {
+    unsafe {
+        [
+            vreinterpretq_f32_u32(vshll_n_u16::<16>(vget_low_u16(halves))),
+            vreinterpretq_f32_u32(vshll_high_n_u16::<16>(halves)),
+        ]
+    }
+}
+
+impl MatrixHalf {
+    pub fn create_from_rows(
+        format: HalfFormat,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let columns = HalfColumns::from_rows(format, rows, intercepts)?;
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        match self.columns.format {
+            HalfFormat::F16 => self.product_neon(values, destination, false, widen_f16),
+            HalfFormat::BF16 => self.product_neon(values, destination, false, widen_bf16),
+        }
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        match self.columns.format {
+            HalfFormat::F16 => self.product_neon(values, destination, true, widen_f16),
+            HalfFormat::BF16 => self.product_neon(values, destination, true, widen_bf16),
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn product_neon(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+        widen: impl Fn(uint16x8_t) -> [float32x4_t; 2],
+    ) {
+        let mut cumulative_sum = 0f32;
+
+        destination
+            .chunks_mut(HALVES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| unsafe {
+                let mut low = vld1q_f32(intercepts.as_ptr());
+                let mut high = vld1q_f32(intercepts[4..].as_ptr());
+                for (val, h) in values.iter().zip(col) {
+                    let [lo, hi] = widen(vld1q_u16(h.as_ptr()));
+                    low = vfmaq_n_f32(low, lo, *val);
+                    high = vfmaq_n_f32(high, hi, *val);
+                }
+                let mut accumulate = [low, high];
+
+                if softmax {
+                    accumulate = [
+                        exp_approx_armf32(accumulate[0]),
+                        exp_approx_armf32(accumulate[1]),
+                    ];
+                    let src: &[f32; HALVES_PER_CHUNK] = transmute(&accumulate);
+                    dst.iter_mut().zip(src).for_each(|(d, s)| {
+                        cumulative_sum += s;
+                        *d = cumulative_sum;
+                    });
+                } else {
+                    // we might have a shorter final slice
+                    let src: &[f32; HALVES_PER_CHUNK] = transmute(&accumulate);
+                    dst.copy_from_slice(&src[0..dst.len()]);
+                }
+            });
+    }
+}
@@ -0,0 +1,121 @@
+use std::arch::aarch64::*;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::packed::{self, Coefficients, PackedColumns};
+
+pub const DOUBLES_PER_INTRINSIC: usize = 2;
+
+// matrix of f64, laid out like `matrix_arm::MatrixF32`: we split the supplied
+// rows into columns of ARM instrinsics (2 x 64-bit floats), and then do a
+// column-wise multiplication
+pub struct MatrixF64 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<float64x2_t>,
+}
+
+pub fn zeros() -> float64x2_t {
+    unsafe { std::mem::transmute([0f64; DOUBLES_PER_INTRINSIC]) }
+}
+
+// safe: an intrinsic is exactly `DOUBLES_PER_INTRINSIC` f64s
+unsafe impl packed::Lanes for float64x2_t {
+    type Elem = f64;
+    const LANES: usize = DOUBLES_PER_INTRINSIC;
+}
+
+impl MatrixF64 {
+    pub fn create_from_rows(rows: &[Vec<f64>], intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f64>,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    #[inline(always)]
+    fn multiply_add(accumulate: &mut float64x2_t, v1: float64x2_t, v2: f64) {
+        // fused multiply-add by a scalar: accumulate + v1 * v2
+        unsafe { *accumulate = vfmaq_n_f64(*accumulate, v1, v2) };
+    }
+
+    pub fn product(&self, values: &[f64], destination: &mut [f64]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.product_neon(values, destination);
+        Ok(())
+    }
+
+    fn product_neon(&self, values: &[f64], destination: &mut [f64]) {
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
+                }
+                // copy to destination -- and we might have a shorter final slice
+                let src: &[f64; DOUBLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
+                dst.copy_from_slice(&src[0..dst.len()]);
+            });
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f64],
+        destination: &mut [f64],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.product_softmax_cumulative_approx_neon(values, destination);
+        Ok(())
+    }
+
+    fn product_softmax_cumulative_approx_neon(&self, values: &[f64], destination: &mut [f64]) {
+        let mut cumulative_sum = 0f64;
+
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`, starting with the intercepts
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
+                }
+
+                // 1. approximate exponential
+                accumulate = crate::exp_approx_arm::exp_approx_armf64(accumulate);
+                // 2. accumulate and copy (taking into account final shorter stub)
+                let src: &[f64; DOUBLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
+                dst.iter_mut().zip(src).for_each(|(d, s)| {
+                    cumulative_sum += s;
+                    *d = cumulative_sum;
+                });
+            });
+    }
+}
@@ -0,0 +1,103 @@
+use std::arch::aarch64::*;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx_arm::exp_approx_armf32;
+use crate::quantize::{QuantizedColumns, BYTES_PER_CHUNK};
+
+// matrix of int8 quantized coefficients, see `quantize`. Each row of a chunk is
+// 8 bytes, which we widen with `vmovl` to 8 x i16, then each half to 4 x i32, and
+// convert to two NEON intrinsics of f32 before the multiply-add.
+pub struct MatrixI8 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: QuantizedColumns,
+}
+
+impl MatrixI8 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_quantized(QuantizedColumns::from_rows(rows, intercepts)?)
+    }
+
+    pub fn create_from_quantized(columns: QuantizedColumns) -> Result<Self, EstimatorError> {
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.product_neon(values, destination, false);
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.product_neon(values, destination, true);
+        Ok(())
+    }
+
+    fn product_neon(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        let sum: f32 = values.iter().sum();
+        let mut cumulative_sum = 0f32;
+
+        destination
+            .chunks_mut(BYTES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (col, scales, zero_points, intercepts))| unsafe {
+                let mut low = vdupq_n_f32(0.0);
+                let mut high = vdupq_n_f32(0.0);
+                for (val, q) in values.iter().zip(col) {
+                    // 8 x i8 -> 8 x i16 -> 2 x (4 x i32) -> 2 x (4 x f32)
+                    let q16 = vmovl_s8(vld1_s8(q.as_ptr()));
+                    low = vfmaq_n_f32(low, vcvtq_f32_s32(vmovl_s16(vget_low_s16(q16))), *val);
+                    high = vfmaq_n_f32(high, vcvtq_f32_s32(vmovl_high_s16(q16)), *val);
+                }
+
+                // scale once for the whole column: intercept + scale * acc - zero point * sum
+                let finish = |acc: float32x4_t, offset: usize| {
+                    let scaled = vfmaq_f32(
+                        vld1q_f32(intercepts[offset..].as_ptr()),
+                        vld1q_f32(scales[offset..].as_ptr()),
+                        acc,
+                    );
+                    vfmsq_n_f32(scaled, vld1q_f32(zero_points[offset..].as_ptr()), sum)
+                };
+                let mut accumulate = [finish(low, 0), finish(high, 4)];
+
+                if softmax {
+                    accumulate = [
+                        exp_approx_armf32(accumulate[0]),
+                        exp_approx_armf32(accumulate[1]),
+                    ];
+                    let src: &[f32; BYTES_PER_CHUNK] = transmute(&accumulate);
+                    dst.iter_mut().zip(src).for_each(|(d, s)| {
+                        cumulative_sum += s;
+                        *d = cumulative_sum;
+                    });
+                } else {
+                    // we might have a shorter final slice
+                    let src: &[f32; BYTES_PER_CHUNK] = transmute(&accumulate);
+                    dst.copy_from_slice(&src[0..dst.len()]);
+                }
+            });
+    }
+}
@@ -1,138 +1,298 @@
 use std::arch::x86_64::*;
 use std::mem::transmute;
 
+use crate::batch;
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_dispatch::Backend;
+use crate::packed::{self, Coefficients, KernelF32, PackedColumns};
+use crate::sparse;
+
 pub const SINGLES_PER_INTRINSIC: usize = 8;
 
 // matrix of f32, but we split the supplied rows into
 // columns of AVX instrinsics (8 x 32-bit floats), and then
 // do a column-wise multiplication
+//
+// the kernels are compiled with `#[target_feature(enable = "avx")]`, so we can't
+// assume the CPU supports them: construction fails if AVX is not available, which
+// means any instance that exists is safe to run.
 pub struct MatrixF32 {
     pub num_columns: usize,
     pub num_col_instrinsics: usize,
     pub num_rows: usize,
-    column_intrinsics: Vec<Vec<__m256>>,
-    intercept_intrinsics: Vec<__m256>,
+    columns: PackedColumns<__m256>,
 }
 
 pub fn zeros() -> __m256 {
     unsafe { std::mem::transmute([0f32; SINGLES_PER_INTRINSIC]) }
 }
 
-impl MatrixF32 {
-    pub fn create_from_rows(rows: &Vec<Vec<f32>>, intercepts: &[f32]) -> Option<Self> {
-        let num_columns = rows.first()?.len();
-        if num_columns != intercepts.len() {
-            return None;
-        }
+// safe: an intrinsic is exactly `SINGLES_PER_INTRINSIC` f32s
+unsafe impl packed::Lanes for __m256 {
+    type Elem = f32;
+    const LANES: usize = SINGLES_PER_INTRINSIC;
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+// the intrinsics for the kernels in `packed`
+impl KernelF32 for __m256 {
+    type Value = __m256;
 
-        let num_col_instrinsics = (num_columns / SINGLES_PER_INTRINSIC)
-            + match num_columns % SINGLES_PER_INTRINSIC {
-                0 => 0,
-                _ => 1,
-            };
-
-        let mut mat = Self {
-            num_columns,
-            num_col_instrinsics,
-            num_rows: rows.len(),
-            column_intrinsics: vec![],
-            intercept_intrinsics: vec![zeros(); num_col_instrinsics],
-        };
-
-        // copy intercepts
-        for (intercept_chunk, dest) in intercepts
-            .chunks(SINGLES_PER_INTRINSIC)
-            .zip(mat.intercept_intrinsics.iter_mut())
-        {
-            let dest_cast: &mut [f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(dest) };
-            dest_cast[..intercept_chunk.len()].copy_from_slice(intercept_chunk);
+    #[inline]
+    #[target_feature(enable = "avx")]
+    unsafe fn broadcast(value: f32) -> __m256 {
+        _mm256_set1_ps(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx")]
+    unsafe fn multiply_add(accumulate: &mut __m256, row: __m256, value: __m256) {
+        // separate multiply add is faster here
+        let mult = _mm256_mul_ps(value, row);
+        *accumulate = _mm256_add_ps(*accumulate, mult);
+        // * not using FMA; it's slower here
+        //accumulate = _mm256_fmadd_ps(val_broad, *row_intrin, accumulate);
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx")]
+    unsafe fn exp(v: __m256) -> __m256 {
+        crate::exp_approx_avx::exp_approx_avxf32(v)
+    }
+
+    // a whole intrinsic directly, or the final shorter stub through an array
+    #[inline]
+    #[target_feature(enable = "avx")]
+    unsafe fn load(src: &[f32]) -> __m256 {
+        if src.len() == SINGLES_PER_INTRINSIC {
+            _mm256_loadu_ps(src.as_ptr())
+        } else {
+            let mut lanes = [0f32; SINGLES_PER_INTRINSIC];
+            lanes[..src.len()].copy_from_slice(src);
+            _mm256_loadu_ps(lanes.as_ptr())
         }
+    }
 
-        // copy coefficients
-        for chunk_num in 0..num_col_instrinsics {
-            let mut col: Vec<__m256> = Vec::new();
-            for r in rows {
-                let chunk = r.chunks(SINGLES_PER_INTRINSIC).nth(chunk_num)?;
-                let mut intrin = [0f32; SINGLES_PER_INTRINSIC];
-                intrin[..chunk.len()].copy_from_slice(chunk);
-                col.push(unsafe { transmute(intrin) });
-            }
-            mat.column_intrinsics.push(col);
+    // a whole intrinsic directly, or the final shorter stub through an array
+    #[inline]
+    #[target_feature(enable = "avx")]
+    unsafe fn store(dst: &mut [f32], v: __m256) {
+        if dst.len() == SINGLES_PER_INTRINSIC {
+            _mm256_storeu_ps(dst.as_mut_ptr(), v)
+        } else {
+            let src: &[f32; SINGLES_PER_INTRINSIC] = transmute(&v);
+            dst.copy_from_slice(&src[..dst.len()]);
         }
+    }
+}
 
-        Some(mat)
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
     }
 
-    #[inline(always)]
-    fn multiply_add(accumulate: &mut __m256, v1: __m256, v2: f32) {
-        unsafe {
-            // broadcast value (since we already have a reference)
-            let val_broad = _mm256_set1_ps(v2);
-            // separate multiply add is faster here
-            let mult = _mm256_mul_ps(val_broad, v1);
-            *accumulate = _mm256_add_ps(*accumulate, mult);
-            // * not using FMA; it's slower here
-            //accumulate = _mm256_fmadd_ps(val_broad, *row_intrin, accumulate);
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Avx));
         }
+
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
     }
 
-    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Option<()> {
-        if destination.len() != self.num_columns || values.len() != self.num_rows {
-            return None;
-        }
+    packed::impl_updates!(f32);
+    packed::impl_read_back!(f32);
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
 
-        destination
-            .chunks_mut(SINGLES_PER_INTRINSIC)
-            .zip(self.column_intrinsics.iter())
-            .zip(self.intercept_intrinsics.iter())
-            .for_each(|((dst, col), intercepts)| {
-                // run multiplication and add to `accumulate`
-                let mut accumulate = *intercepts;
-                for (val, row_intrin) in values.iter().zip(col) {
-                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
-                }
-                // copy to destination (by interpreting the intrinsic as a slice) -- and we might
-                // have a shorter final slice
-                let src: &[f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
-                dst.copy_from_slice(&src[0..dst.len()]);
-            });
-
-        Some(())
+        // safe: AVX support was checked on construction
+        unsafe { self.product_avx(values, destination, false) };
+        Ok(())
     }
 
     pub fn product_softmax_cumulative_approx(
         &self,
         values: &[f32],
         destination: &mut [f32],
-    ) -> Option<()> {
-        if destination.len() != self.num_columns || values.len() != self.num_rows {
-            return None;
-        }
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX support was checked on construction
+        unsafe { self.product_avx(values, destination, true) };
+        Ok(())
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            output_indices.len(),
+        )?;
 
-        let mut cumulative_sum = 0f32;
-
-        destination
-            .chunks_mut(SINGLES_PER_INTRINSIC)
-            .zip(self.column_intrinsics.iter())
-            .zip(self.intercept_intrinsics.iter())
-            .for_each(|((dst, col), intercepts)| {
-                // run multiplication and add to `accumulate`, starting with the intercepts
-                let mut accumulate = *intercepts;
-                for (val, row_intrin) in values.iter().zip(col) {
-                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
-                }
-
-                // copy to destination (taking into account final shorter stub) and apply cumulative softmax
-                // 1. approximate exponential
-                accumulate = crate::exp_approx_avx::exp_approx_avxf32(accumulate);
-                // 2. accumulate and copy
-                let src: &[f32; SINGLES_PER_INTRINSIC] = unsafe { transmute(&accumulate) };
-                dst.iter_mut().zip(src).for_each(|(d, s)| {
-                    cumulative_sum += s;
-                    *d = cumulative_sum;
-                });
-            });
-
-        Some(())
+        // safe: AVX support was checked on construction
+        unsafe { self.selected_avx(values, output_indices, destination) }
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        let mut destination = [0f32];
+        self.product_selected(values, &[output], &mut destination)?;
+        Ok(destination[0])
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one
+    // value per output, as `Scorer` does when an input changes
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        // safe: AVX support was checked on construction
+        unsafe { self.scaled_row_avx(input, delta, destination) }
+    }
+
+    // `inputs` holds `n_rows` input vectors one after the other, and `outputs`
+    // receives `n_rows` output vectors in the same way
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: AVX support was checked on construction
+        unsafe { self.batch_avx(inputs, outputs, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: AVX support was checked on construction
+        unsafe { self.batch_avx(inputs, outputs, true) };
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; everything else is skipped
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX support was checked on construction, and the indices above
+        unsafe { self.sparse_avx(indices, values, destination, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX support was checked on construction, and the indices above
+        unsafe { self.sparse_avx(indices, values, destination, true) };
+        Ok(())
+    }
+
+    // the kernels in `packed`, compiled for AVX so the intrinsics above are inlined
+
+    #[target_feature(enable = "avx")]
+    unsafe fn product_avx(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        self.columns.product(values, destination, softmax)
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn selected_avx(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns
+            .product_selected(values, output_indices, destination)
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn scaled_row_avx(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns.add_scaled_row(input, delta, destination)
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn batch_avx(&self, inputs: &[f32], outputs: &mut [f32], softmax: bool) {
+        self.columns.product_batch(inputs, outputs, softmax)
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn sparse_avx(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+    ) {
+        self.columns
+            .product_sparse(indices, values, destination, softmax)
     }
 }
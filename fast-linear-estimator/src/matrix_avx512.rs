@@ -0,0 +1,290 @@
+use std::arch::x86_64::*;
+
+use crate::batch;
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_dispatch::Backend;
+use crate::packed::{self, Coefficients, KernelF32, PackedColumns};
+use crate::sparse;
+
+pub const SINGLES_PER_INTRINSIC: usize = 16;
+
+// matrix of f32, but we split the supplied rows into
+// columns of AVX-512 instrinsics (16 x 32-bit floats), and then
+// do a column-wise multiplication
+//
+// same approach as `matrix_avx`, but with twice the width. The coefficients are
+// read from the `PackedColumns` copy, which is padded with zeros, so every load is
+// a whole intrinsic; only the writes to the caller's buffer need care, and the
+// final partial chunk is written with a masked store rather than copying through
+// an array, for the softmax too. Construction fails if AVX-512F is not available.
+pub struct MatrixF32 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<__m512>,
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("avx512f")
+}
+
+// safe: an intrinsic is exactly `SINGLES_PER_INTRINSIC` f32s
+unsafe impl packed::Lanes for __m512 {
+    type Elem = f32;
+    const LANES: usize = SINGLES_PER_INTRINSIC;
+}
+
+// mask with the lowest `len` lanes set
+#[inline(always)]
+fn lane_mask(len: usize) -> __mmask16 {
+    debug_assert!(len <= SINGLES_PER_INTRINSIC);
+    ((1u32 << len) - 1) as __mmask16
+}
+
+// the intrinsics for the kernels in `packed`
+impl KernelF32 for __m512 {
+    type Value = __m512;
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn broadcast(value: f32) -> __m512 {
+        _mm512_set1_ps(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn multiply_add(accumulate: &mut __m512, row: __m512, value: __m512) {
+        // separate multiply and add as for AVX
+        let mult = _mm512_mul_ps(value, row);
+        *accumulate = _mm512_add_ps(*accumulate, mult);
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn exp(v: __m512) -> __m512 {
+        crate::exp_approx_avx512::exp_approx_avx512f32(v)
+    }
+
+    // lanes beyond `src.len()` are not read, and are zero
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn load(src: &[f32]) -> __m512 {
+        _mm512_maskz_loadu_ps(lane_mask(src.len()), src.as_ptr())
+    }
+
+    // lanes beyond `dst.len()` are not written, so the masked store handles the
+    // final shorter stub
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn store(dst: &mut [f32], v: __m512) {
+        _mm512_mask_storeu_ps(dst.as_mut_ptr(), lane_mask(dst.len()), v)
+    }
+}
+
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Avx512));
+        }
+
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    packed::impl_updates!(f32);
+    packed::impl_read_back!(f32);
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.product_avx512(values, destination, false) };
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.product_avx512(values, destination, true) };
+        Ok(())
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            output_indices.len(),
+        )?;
+
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.selected_avx512(values, output_indices, destination) }
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        let mut destination = [0f32];
+        self.product_selected(values, &[output], &mut destination)?;
+        Ok(destination[0])
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one
+    // value per output, as `Scorer` does when an input changes
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.scaled_row_avx512(input, delta, destination) }
+    }
+
+    // `inputs` holds `n_rows` input vectors one after the other, and `outputs`
+    // receives `n_rows` output vectors in the same way
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.batch_avx512(inputs, outputs, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: AVX-512F support was checked on construction
+        unsafe { self.batch_avx512(inputs, outputs, true) };
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; everything else is skipped
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX-512F support was checked on construction, and the indices above
+        unsafe { self.sparse_avx512(indices, values, destination, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX-512F support was checked on construction, and the indices above
+        unsafe { self.sparse_avx512(indices, values, destination, true) };
+        Ok(())
+    }
+
+    // the kernels in `packed`, compiled for AVX-512F so the intrinsics above are inlined
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn product_avx512(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        self.columns.product(values, destination, softmax)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn selected_avx512(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns
+            .product_selected(values, output_indices, destination)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn scaled_row_avx512(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns.add_scaled_row(input, delta, destination)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn batch_avx512(&self, inputs: &[f32], outputs: &mut [f32], softmax: bool) {
+        self.columns.product_batch(inputs, outputs, softmax)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn sparse_avx512(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+    ) {
+        self.columns
+            .product_sparse(indices, values, destination, softmax)
+    }
+}
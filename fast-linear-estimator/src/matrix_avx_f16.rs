@@ -0,0 +1,124 @@
+use std::arch::x86_64::*;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::half::{HalfColumns, HalfFormat, HALVES_PER_CHUNK};
+use crate::matrix_dispatch::Backend;
+
+// matrix of 16-bit (f16 or bf16) coefficients, see `half`. Each row of a chunk is
+// 8 halves, which we widen to an AVX intrinsic of f32 before the multiply-add, so
+// there's half the coefficient memory to read of `matrix_avx::MatrixF32`:
+//
+//  * f16 with the F16C conversion, `_mm256_cvtph_ps`
+//  * bf16 by zero extending to 32 bits and shifting into the top half (AVX2)
+//
+// construction fails unless the CPU has both; any instance that exists is safe to run.
+pub struct MatrixHalf {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: HalfColumns,
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("avx2") && is_x86_feature_detected!("f16c")
+}
+
+impl MatrixHalf {
+    pub fn create_from_rows(
+        format: HalfFormat,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Avx));
+        }
+
+        let columns = HalfColumns::from_rows(format, rows, intercepts)?;
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 and F16C support was checked on construction
+        unsafe {
+            match self.columns.format {
+                HalfFormat::F16 => self.product_avx::<false>(values, destination, false),
+                HalfFormat::BF16 => self.product_avx::<true>(values, destination, false),
+            }
+        };
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 and F16C support was checked on construction
+        unsafe {
+            match self.columns.format {
+                HalfFormat::F16 => self.product_avx::<false>(values, destination, true),
+                HalfFormat::BF16 => self.product_avx::<true>(values, destination, true),
+            }
+        };
+        Ok(())
+    }
+
+    // one copy of the kernel for each format, so the widening isn't a branch
+    #[target_feature(enable = "avx2,f16c")]
+    unsafe fn product_avx<const BF16: bool>(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+    ) {
+        let mut cumulative_sum = 0f32;
+
+        destination
+            .chunks_mut(HALVES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                let mut accumulate = _mm256_loadu_ps(intercepts.as_ptr());
+                for (val, h) in values.iter().zip(col) {
+                    let halves = _mm_loadu_si128(h.as_ptr() as *const __m128i);
+                    let widened = if BF16 {
+                        _mm256_castsi256_ps(_mm256_slli_epi32::<16>(_mm256_cvtepu16_epi32(halves)))
+                    } else {
+                        _mm256_cvtph_ps(halves)
+                    };
+                    let mult = _mm256_mul_ps(_mm256_set1_ps(*val), widened);
+                    accumulate = _mm256_add_ps(accumulate, mult);
+                }
+
+                if softmax {
+                    accumulate = crate::exp_approx_avx::exp_approx_avxf32(accumulate);
+                    let src: &[f32; HALVES_PER_CHUNK] = transmute(&accumulate);
+                    dst.iter_mut().zip(src).for_each(|(d, s)| {
+                        cumulative_sum += s;
+                        *d = cumulative_sum;
+                    });
+                } else {
+                    // we might have a shorter final slice
+                    let src: &[f32; HALVES_PER_CHUNK] = transmute(&accumulate);
+                    dst.copy_from_slice(&src[0..dst.len()]);
+                }
+            });
+    }
+}
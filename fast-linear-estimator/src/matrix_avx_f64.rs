@@ -0,0 +1,144 @@
+use std::arch::x86_64::*;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_dispatch::Backend;
+use crate::packed::{self, Coefficients, PackedColumns};
+
+pub const DOUBLES_PER_INTRINSIC: usize = 4;
+
+// matrix of f64, laid out like `matrix_avx::MatrixF32`: we split the supplied
+// rows into columns of AVX instrinsics (4 x 64-bit floats), and then do a
+// column-wise multiplication
+//
+// the exponential needs 64-bit integer operations, which came with AVX2, so
+// construction fails unless the CPU has AVX2; any instance that exists is safe
+// to run.
+pub struct MatrixF64 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<__m256d>,
+}
+
+pub fn zeros() -> __m256d {
+    unsafe { std::mem::transmute([0f64; DOUBLES_PER_INTRINSIC]) }
+}
+
+// safe: an intrinsic is exactly `DOUBLES_PER_INTRINSIC` f64s
+unsafe impl packed::Lanes for __m256d {
+    type Elem = f64;
+    const LANES: usize = DOUBLES_PER_INTRINSIC;
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+impl MatrixF64 {
+    pub fn create_from_rows(rows: &[Vec<f64>], intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f64>,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Avx));
+        }
+
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn multiply_add(accumulate: &mut __m256d, v1: __m256d, v2: f64) {
+        let val_broad = _mm256_set1_pd(v2);
+        let mult = _mm256_mul_pd(val_broad, v1);
+        *accumulate = _mm256_add_pd(*accumulate, mult);
+    }
+
+    pub fn product(&self, values: &[f64], destination: &mut [f64]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 support was checked on construction
+        unsafe { self.product_avx(values, destination) };
+        Ok(())
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn product_avx(&self, values: &[f64], destination: &mut [f64]) {
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
+                }
+                // copy to destination -- and we might have a shorter final slice
+                let src: &[f64; DOUBLES_PER_INTRINSIC] = transmute(&accumulate);
+                dst.copy_from_slice(&src[0..dst.len()]);
+            });
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f64],
+        destination: &mut [f64],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 support was checked on construction
+        unsafe { self.product_softmax_cumulative_approx_avx(values, destination) };
+        Ok(())
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn product_softmax_cumulative_approx_avx(
+        &self,
+        values: &[f64],
+        destination: &mut [f64],
+    ) {
+        let mut cumulative_sum = 0f64;
+
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`, starting with the intercepts
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, *row_intrin, *val);
+                }
+
+                // 1. approximate exponential
+                accumulate = crate::exp_approx_avx::exp_approx_avxf64(accumulate);
+                // 2. accumulate and copy (taking into account final shorter stub)
+                let src: &[f64; DOUBLES_PER_INTRINSIC] = transmute(&accumulate);
+                dst.iter_mut().zip(src).for_each(|(d, s)| {
+                    cumulative_sum += s;
+                    *d = cumulative_sum;
+                });
+            });
+    }
+}
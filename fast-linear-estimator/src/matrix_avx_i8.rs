@@ -0,0 +1,111 @@
+use std::arch::x86_64::*;
+use std::mem::transmute;
+
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_dispatch::Backend;
+use crate::quantize::{QuantizedColumns, BYTES_PER_CHUNK};
+
+// matrix of int8 quantized coefficients, see `quantize`. Each row of a chunk is
+// 8 bytes, which we widen to 8 x i32 and convert to an AVX intrinsic of f32 before
+// the multiply-add, so there's 4x less coefficient memory to read than
+// `matrix_avx::MatrixF32`.
+//
+// the widening needs AVX2, so construction fails unless the CPU has it; any
+// instance that exists is safe to run.
+pub struct MatrixI8 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: QuantizedColumns,
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+impl MatrixI8 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_quantized(QuantizedColumns::from_rows(rows, intercepts)?)
+    }
+
+    pub fn create_from_quantized(columns: QuantizedColumns) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Avx));
+        }
+
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 support was checked on construction
+        unsafe { self.product_avx(values, destination, false) };
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: AVX2 support was checked on construction
+        unsafe { self.product_avx(values, destination, true) };
+        Ok(())
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn product_avx(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        let sum = _mm256_set1_ps(values.iter().sum());
+        let mut cumulative_sum = 0f32;
+
+        destination
+            .chunks_mut(BYTES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (col, scales, zero_points, intercepts))| {
+                let mut accumulate = _mm256_setzero_ps();
+                for (val, q) in values.iter().zip(col) {
+                    // 8 x i8 -> 8 x i32 -> 8 x f32
+                    let q32 = _mm256_cvtepi8_epi32(_mm_loadl_epi64(q.as_ptr() as *const __m128i));
+                    let mult = _mm256_mul_ps(_mm256_set1_ps(*val), _mm256_cvtepi32_ps(q32));
+                    accumulate = _mm256_add_ps(accumulate, mult);
+                }
+
+                // scale once for the whole column: intercept + scale * acc - zero point * sum
+                let scaled = _mm256_mul_ps(_mm256_loadu_ps(scales.as_ptr()), accumulate);
+                let offset = _mm256_sub_ps(
+                    _mm256_loadu_ps(intercepts.as_ptr()),
+                    _mm256_mul_ps(_mm256_loadu_ps(zero_points.as_ptr()), sum),
+                );
+                accumulate = _mm256_add_ps(scaled, offset);
+
+                if softmax {
+                    accumulate = crate::exp_approx_avx::exp_approx_avxf32(accumulate);
+                    let src: &[f32; BYTES_PER_CHUNK] = transmute(&accumulate);
+                    dst.iter_mut().zip(src).for_each(|(d, s)| {
+                        cumulative_sum += s;
+                        *d = cumulative_sum;
+                    });
+                } else {
+                    // we might have a shorter final slice
+                    let src: &[f32; BYTES_PER_CHUNK] = transmute(&accumulate);
+                    dst.copy_from_slice(&src[0..dst.len()]);
+                }
+            });
+    }
+}
@@ -0,0 +1,817 @@
+use crate::error::EstimatorError;
+use crate::half::HalfFormat;
+#[cfg(target_arch = "aarch64")]
+use crate::matrix_arm;
+#[cfg(target_arch = "aarch64")]
+use crate::matrix_arm_f16;
+#[cfg(target_arch = "aarch64")]
+use crate::matrix_arm_f64;
+#[cfg(target_arch = "aarch64")]
+use crate::matrix_arm_i8;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx512;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx_f16;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx_f64;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_avx_i8;
+use crate::matrix_scalar;
+use crate::matrix_scalar_f16;
+use crate::matrix_scalar_f64;
+use crate::matrix_scalar_i8;
+#[cfg(target_arch = "x86_64")]
+use crate::matrix_sse;
+use crate::packed::Coefficients;
+use crate::quantize::{QuantizationReport, QuantizedColumns};
+
+// the implementations we can choose between; not all of them are available on
+// every target, and on x86_64 it depends on what the CPU supports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Avx512,
+    Avx,
+    Sse,
+    Neon,
+    Scalar,
+}
+
+impl Backend {
+    pub fn is_supported(self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx512 => matrix_avx512::is_supported(),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx => matrix_avx::is_supported(),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse => matrix_sse::is_supported(),
+            // NEON is mandatory on aarch64
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => true,
+            Backend::Scalar => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+// probe the CPU and pick the fastest supported implementation
+pub fn detect_backend() -> Backend {
+    [Backend::Avx512, Backend::Avx, Backend::Sse, Backend::Neon]
+        .iter()
+        .copied()
+        .find(|b| b.is_supported())
+        .unwrap_or(Backend::Scalar)
+}
+
+// create with the first of `backends` that's supported, or else the scalar one; any
+// other error is returned straight away, since every backend would give the same
+fn first_supported<T>(
+    backends: &[Backend],
+    mut create: impl FnMut(Backend) -> Result<T, EstimatorError>,
+) -> Result<T, EstimatorError> {
+    for &backend in backends {
+        match create(backend) {
+            Err(EstimatorError::Unsupported(_)) => continue,
+            result => return result,
+        }
+    }
+    create(Backend::Scalar)
+}
+
+// run the same expression against whichever implementation is in use
+macro_rules! with_inner {
+    ($inner:expr, $m:ident => $e:expr) => {
+        match $inner {
+            #[cfg(target_arch = "x86_64")]
+            Inner::Avx512($m) => $e,
+            #[cfg(target_arch = "x86_64")]
+            Inner::Avx($m) => $e,
+            #[cfg(target_arch = "x86_64")]
+            Inner::Sse($m) => $e,
+            #[cfg(target_arch = "aarch64")]
+            Inner::Neon($m) => $e,
+            Inner::Scalar($m) => $e,
+        }
+    };
+}
+
+enum Inner {
+    #[cfg(target_arch = "x86_64")]
+    Avx512(matrix_avx512::MatrixF32),
+    #[cfg(target_arch = "x86_64")]
+    Avx(matrix_avx::MatrixF32),
+    #[cfg(target_arch = "x86_64")]
+    Sse(matrix_sse::MatrixF32),
+    #[cfg(target_arch = "aarch64")]
+    Neon(matrix_arm::MatrixF32),
+    Scalar(matrix_scalar::MatrixF32),
+}
+
+// matrix of f32 that picks its implementation when it's created, based on what
+// the CPU supports, instead of assuming AVX is always there. The check happens
+// once, so the only per-call cost is a match on the variant.
+pub struct MatrixF32 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    inner: Inner,
+}
+
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(detect_backend(), Coefficients::Rows(rows), intercepts)
+    }
+
+    // one slice per input, holding one coefficient per output
+    pub fn from_slices(rows: &[&[f32]], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(detect_backend(), Coefficients::Slices(rows), intercepts)
+    }
+
+    // flat, with the outputs for each input contiguous: `data[input * num_outputs + output]`
+    pub fn from_row_major(
+        data: &[f32],
+        num_inputs: usize,
+        num_outputs: usize,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let coefficients = Coefficients::RowMajor {
+            data,
+            num_inputs,
+            num_outputs,
+        };
+        Self::create_from_coefficients(detect_backend(), coefficients, intercepts)
+    }
+
+    // flat, with the inputs for each output contiguous: `data[output * num_inputs + input]`
+    pub fn from_column_major(
+        data: &[f32],
+        num_inputs: usize,
+        num_outputs: usize,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let coefficients = Coefficients::ColumnMajor {
+            data,
+            num_inputs,
+            num_outputs,
+        };
+        Self::create_from_coefficients(detect_backend(), coefficients, intercepts)
+    }
+
+    // as above, but first checks that every coefficient and intercept is finite: a
+    // single NaN would otherwise silently poison every output
+    pub fn create_validated(
+        source: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        source.check_finite(intercepts)?;
+        Self::create_from_coefficients(detect_backend(), source, intercepts)
+    }
+
+    // for a model trained on standardised inputs, `(x - mean) / scale` with one mean
+    // and scale per input: the standardisation is folded into the coefficients and
+    // intercepts, so raw inputs can be passed straight to the product
+    pub fn create_standardised(
+        source: Coefficients<f32>,
+        intercepts: &[f32],
+        mean: &[f32],
+        scale: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        Self::create_standardised_with_backend(detect_backend(), source, intercepts, mean, scale)
+    }
+
+    // as above, failing with `EstimatorError::Unsupported` if the backend is not
+    // supported on this machine
+    pub fn create_standardised_with_backend(
+        backend: Backend,
+        source: Coefficients<f32>,
+        intercepts: &[f32],
+        mean: &[f32],
+        scale: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let (num_inputs, num_outputs) = source.dimensions()?;
+        let (data, intercepts) = source.fold_standardisation(intercepts, mean, scale)?;
+        let folded = Coefficients::RowMajor {
+            data: &data,
+            num_inputs,
+            num_outputs,
+        };
+        Self::create_from_coefficients(backend, folded, &intercepts)
+    }
+
+    // fails with `EstimatorError::Unsupported` if the backend is not supported on
+    // this machine
+    pub fn create_with_backend(
+        backend: Backend,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(backend, Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        backend: Backend,
+        source: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let inner = match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx512 => Inner::Avx512(matrix_avx512::MatrixF32::create_from_coefficients(
+                source, intercepts,
+            )?),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx => Inner::Avx(matrix_avx::MatrixF32::create_from_coefficients(
+                source, intercepts,
+            )?),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse => Inner::Sse(matrix_sse::MatrixF32::create_from_coefficients(
+                source, intercepts,
+            )?),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => Inner::Neon(matrix_arm::MatrixF32::create_from_coefficients(
+                source, intercepts,
+            )?),
+            Backend::Scalar => Inner::Scalar(matrix_scalar::MatrixF32::create_from_coefficients(
+                source, intercepts,
+            )?),
+            #[allow(unreachable_patterns)]
+            _ => return Err(EstimatorError::Unsupported(backend)),
+        };
+
+        let (num_columns, num_rows) = with_inner!(&inner, m => (m.num_columns, m.num_rows));
+
+        Ok(Self {
+            num_columns,
+            num_rows,
+            inner,
+        })
+    }
+
+    pub fn backend(&self) -> Backend {
+        match &self.inner {
+            #[cfg(target_arch = "x86_64")]
+            Inner::Avx512(_) => Backend::Avx512,
+            #[cfg(target_arch = "x86_64")]
+            Inner::Avx(_) => Backend::Avx,
+            #[cfg(target_arch = "x86_64")]
+            Inner::Sse(_) => Backend::Sse,
+            #[cfg(target_arch = "aarch64")]
+            Inner::Neon(_) => Backend::Neon,
+            Inner::Scalar(_) => Backend::Scalar,
+        }
+    }
+
+    // in-place updates, written straight into the packed coefficients
+    pub fn set_intercepts(&mut self, intercepts: &[f32]) -> Result<(), EstimatorError> {
+        with_inner!(&mut self.inner, m => m.set_intercepts(intercepts))
+    }
+
+    pub fn add_to_intercepts(&mut self, deltas: &[f32]) -> Result<(), EstimatorError> {
+        with_inner!(&mut self.inner, m => m.add_to_intercepts(deltas))
+    }
+
+    pub fn set_input_row(
+        &mut self,
+        input: usize,
+        coefficients: &[f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&mut self.inner, m => m.set_input_row(input, coefficients))
+    }
+
+    pub fn set_coefficient(
+        &mut self,
+        input: usize,
+        output: usize,
+        value: f32,
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&mut self.inner, m => m.set_coefficient(input, output, value))
+    }
+
+    // reading back the packed coefficients, without the padding
+    pub fn coefficient(&self, input: usize, output: usize) -> Option<f32> {
+        with_inner!(&self.inner, m => m.coefficient(input, output))
+    }
+
+    pub fn intercepts(&self) -> Vec<f32> {
+        with_inner!(&self.inner, m => m.intercepts())
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<f32>> {
+        with_inner!(&self.inner, m => m.to_rows())
+    }
+
+    // (input, output, coefficient) for every coefficient, an input at a time
+    pub fn coefficients(&self) -> Box<dyn Iterator<Item = (usize, usize, f32)> + '_> {
+        with_inner!(&self.inner, m => Box::new(m.coefficients()))
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.product(values, destination))
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.product_softmax_cumulative_approx(values, destination))
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.product_selected(values, output_indices, destination))
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        with_inner!(&self.inner, m => m.product_output(values, output))
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one value
+    // per output
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.add_scaled_input_row(input, delta, destination))
+    }
+
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.product_batch(inputs, n_rows, outputs))
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.softmax_cumulative_batch(inputs, n_rows, outputs))
+    }
+
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.product_sparse(indices, values, destination))
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner!(&self.inner, m => m.softmax_cumulative_sparse(indices, values, destination))
+    }
+
+    // split across threads; see `LinearEstimator::par_product_batch`
+    #[cfg(feature = "parallel")]
+    pub fn par_product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+        min_chunk_rows: usize,
+    ) -> Result<(), EstimatorError> {
+        crate::parallel::par_batch(self, inputs, n_rows, outputs, min_chunk_rows, |m, i, n, o| {
+            m.product_batch(i, n, o)
+        })
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn par_softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+        min_chunk_rows: usize,
+    ) -> Result<(), EstimatorError> {
+        crate::parallel::par_batch(self, inputs, n_rows, outputs, min_chunk_rows, |m, i, n, o| {
+            m.softmax_cumulative_batch(i, n, o)
+        })
+    }
+}
+
+// the f64 implementations; fewer of them, since there is no f64 AVX-512 or SSE
+// version
+enum InnerF64 {
+    #[cfg(target_arch = "x86_64")]
+    Avx(matrix_avx_f64::MatrixF64),
+    #[cfg(target_arch = "aarch64")]
+    Neon(matrix_arm_f64::MatrixF64),
+    Scalar(matrix_scalar_f64::MatrixF64),
+}
+
+macro_rules! with_inner_f64 {
+    ($inner:expr, $m:ident => $e:expr) => {
+        match $inner {
+            #[cfg(target_arch = "x86_64")]
+            InnerF64::Avx($m) => $e,
+            #[cfg(target_arch = "aarch64")]
+            InnerF64::Neon($m) => $e,
+            InnerF64::Scalar($m) => $e,
+        }
+    };
+}
+
+// matrix of f64, picking its implementation when it's created in the same way
+// as `MatrixF32`. On x86_64 the AVX version needs AVX2.
+pub struct MatrixF64 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    inner: InnerF64,
+}
+
+impl MatrixF64 {
+    pub fn create_from_rows(rows: &[Vec<f64>], intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        Self::create_from(Coefficients::Rows(rows), intercepts)
+    }
+
+    // as for `MatrixF32`
+    pub fn from_slices(rows: &[&[f64]], intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        Self::create_from(Coefficients::Slices(rows), intercepts)
+    }
+
+    pub fn from_row_major(
+        data: &[f64],
+        num_inputs: usize,
+        num_outputs: usize,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        let coefficients = Coefficients::RowMajor {
+            data,
+            num_inputs,
+            num_outputs,
+        };
+        Self::create_from(coefficients, intercepts)
+    }
+
+    pub fn from_column_major(
+        data: &[f64],
+        num_inputs: usize,
+        num_outputs: usize,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        let coefficients = Coefficients::ColumnMajor {
+            data,
+            num_inputs,
+            num_outputs,
+        };
+        Self::create_from(coefficients, intercepts)
+    }
+
+    // as for `MatrixF32`
+    pub fn create_validated(
+        source: Coefficients<f64>,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        source.check_finite(intercepts)?;
+        Self::create_from(source, intercepts)
+    }
+
+    // the first supported backend
+    fn create_from(source: Coefficients<f64>, intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        first_supported(&[Backend::Avx, Backend::Neon], |b| {
+            Self::create_from_coefficients(b, source, intercepts)
+        })
+    }
+
+    // fails with `EstimatorError::Unsupported` if the backend is not supported on
+    // this machine, or has no f64 implementation
+    pub fn create_with_backend(
+        backend: Backend,
+        rows: &[Vec<f64>],
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(backend, Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        backend: Backend,
+        source: Coefficients<f64>,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        let inner = match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx => InnerF64::Avx(matrix_avx_f64::MatrixF64::create_from_coefficients(
+                source, intercepts,
+            )?),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => InnerF64::Neon(matrix_arm_f64::MatrixF64::create_from_coefficients(
+                source, intercepts,
+            )?),
+            Backend::Scalar => InnerF64::Scalar(
+                matrix_scalar_f64::MatrixF64::create_from_coefficients(source, intercepts)?,
+            ),
+            #[allow(unreachable_patterns)]
+            _ => return Err(EstimatorError::Unsupported(backend)),
+        };
+
+        let (num_columns, num_rows) = with_inner_f64!(&inner, m => (m.num_columns, m.num_rows));
+
+        Ok(Self {
+            num_columns,
+            num_rows,
+            inner,
+        })
+    }
+
+    pub fn backend(&self) -> Backend {
+        match &self.inner {
+            #[cfg(target_arch = "x86_64")]
+            InnerF64::Avx(_) => Backend::Avx,
+            #[cfg(target_arch = "aarch64")]
+            InnerF64::Neon(_) => Backend::Neon,
+            InnerF64::Scalar(_) => Backend::Scalar,
+        }
+    }
+
+    pub fn product(&self, values: &[f64], destination: &mut [f64]) -> Result<(), EstimatorError> {
+        with_inner_f64!(&self.inner, m => m.product(values, destination))
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f64],
+        destination: &mut [f64],
+    ) -> Result<(), EstimatorError> {
+        with_inner_f64!(&self.inner, m => m.product_softmax_cumulative_approx(values, destination))
+    }
+}
+
+// the int8 quantized implementations; as for f64, the x86_64 version needs AVX2
+enum InnerI8 {
+    #[cfg(target_arch = "x86_64")]
+    Avx(matrix_avx_i8::MatrixI8),
+    #[cfg(target_arch = "aarch64")]
+    Neon(matrix_arm_i8::MatrixI8),
+    Scalar(matrix_scalar_i8::MatrixI8),
+}
+
+macro_rules! with_inner_i8 {
+    ($inner:expr, $m:ident => $e:expr) => {
+        match $inner {
+            #[cfg(target_arch = "x86_64")]
+            InnerI8::Avx($m) => $e,
+            #[cfg(target_arch = "aarch64")]
+            InnerI8::Neon($m) => $e,
+            InnerI8::Scalar($m) => $e,
+        }
+    };
+}
+
+// matrix with int8 quantized coefficients, f32 inputs and outputs; picks its
+// implementation when it's created in the same way as `MatrixF32`
+pub struct MatrixI8 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    inner: InnerI8,
+}
+
+// as `Backend::is_supported`, for the int8 implementations; on x86_64 this needs AVX2
+fn supports_i8(backend: Backend) -> bool {
+    match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx => matrix_avx_i8::is_supported(),
+        #[cfg(target_arch = "aarch64")]
+        Backend::Neon => true,
+        Backend::Scalar => true,
+        #[allow(unreachable_patterns)]
+        _ => false,
+    }
+}
+
+fn detect_backend_i8() -> Backend {
+    [Backend::Avx, Backend::Neon]
+        .iter()
+        .copied()
+        .find(|&b| supports_i8(b))
+        .unwrap_or(Backend::Scalar)
+}
+
+impl MatrixI8 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_with_backend(detect_backend_i8(), rows, intercepts)
+    }
+
+    // fails with `EstimatorError::Unsupported` if the backend is not supported on
+    // this machine, or has no int8 implementation
+    pub fn create_with_backend(
+        backend: Backend,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        Self::from_quantized(backend, QuantizedColumns::from_rows(rows, intercepts)?)
+    }
+
+    fn from_quantized(backend: Backend, columns: QuantizedColumns) -> Result<Self, EstimatorError> {
+        let inner = match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx => InnerI8::Avx(matrix_avx_i8::MatrixI8::create_from_quantized(columns)?),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => {
+                InnerI8::Neon(matrix_arm_i8::MatrixI8::create_from_quantized(columns)?)
+            }
+            Backend::Scalar => {
+                InnerI8::Scalar(matrix_scalar_i8::MatrixI8::create_from_quantized(columns)?)
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(EstimatorError::Unsupported(backend)),
+        };
+
+        let (num_columns, num_rows) = with_inner_i8!(&inner, m => (m.num_columns, m.num_rows));
+
+        Ok(Self {
+            num_columns,
+            num_rows,
+            inner,
+        })
+    }
+
+    // quantize, and measure how far the result is from the `MatrixF32` with the
+    // same coefficients: both per coefficient, and in the output of `product`
+    // over `sample_inputs`
+    pub fn quantize(
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+        sample_inputs: &[Vec<f32>],
+    ) -> Result<(Self, QuantizationReport), EstimatorError> {
+        let original = MatrixF32::create_from_rows(rows, intercepts)?;
+        let columns = QuantizedColumns::from_rows(rows, intercepts)?;
+
+        let mut max_coefficient_error = 0f32;
+        for (input, r) in rows.iter().enumerate() {
+            for (output, c) in r.iter().enumerate() {
+                let error = (columns.dequantized(input, output) - c).abs();
+                max_coefficient_error = max_coefficient_error.max(error);
+            }
+        }
+
+        let quantized = Self::from_quantized(detect_backend_i8(), columns)?;
+
+        let mut max_abs_error = 0f32;
+        let mut expected = vec![0f32; original.num_columns];
+        let mut actual = vec![0f32; original.num_columns];
+        for values in sample_inputs {
+            original.product(values, &mut expected)?;
+            quantized.product(values, &mut actual)?;
+            for (e, a) in expected.iter().zip(&actual) {
+                max_abs_error = max_abs_error.max((e - a).abs());
+            }
+        }
+
+        let report = QuantizationReport {
+            max_coefficient_error,
+            max_abs_error,
+        };
+        Ok((quantized, report))
+    }
+
+    pub fn backend(&self) -> Backend {
+        match &self.inner {
+            #[cfg(target_arch = "x86_64")]
+            InnerI8::Avx(_) => Backend::Avx,
+            #[cfg(target_arch = "aarch64")]
+            InnerI8::Neon(_) => Backend::Neon,
+            InnerI8::Scalar(_) => Backend::Scalar,
+        }
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        with_inner_i8!(&self.inner, m => m.product(values, destination))
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        with_inner_i8!(&self.inner, m => m.product_softmax_cumulative_approx(values, destination))
+    }
+}
+
+// the 16-bit implementations, shared by `MatrixF16` and `MatrixBF16`; the x86_64
+// version needs AVX2 and F16C
+enum InnerHalf {
+    #[cfg(target_arch = "x86_64")]
+    Avx(matrix_avx_f16::MatrixHalf),
+    #[cfg(target_arch = "aarch64")]
+    Neon(matrix_arm_f16::MatrixHalf),
+    Scalar(matrix_scalar_f16::MatrixHalf),
+}
+
+macro_rules! with_inner_half {
+    ($inner:expr, $m:ident => $e:expr) => {
+        match $inner {
+            #[cfg(target_arch = "x86_64")]
+            InnerHalf::Avx($m) => $e,
+            #[cfg(target_arch = "aarch64")]
+            InnerHalf::Neon($m) => $e,
+            InnerHalf::Scalar($m) => $e,
+        }
+    };
+}
+
+// a matrix of 16-bit coefficients in the given format, with f32 inputs and outputs,
+// picking its implementation when it's created in the same way as `MatrixF32`
+macro_rules! half_matrix {
+    ($name:ident, $format:expr) => {
+        pub struct $name {
+            pub num_columns: usize,
+            pub num_rows: usize,
+            inner: InnerHalf,
+        }
+
+        impl $name {
+            pub fn create_from_rows(
+                rows: &[Vec<f32>],
+                intercepts: &[f32],
+            ) -> Result<Self, EstimatorError> {
+                first_supported(&[Backend::Avx, Backend::Neon], |b| {
+                    Self::create_with_backend(b, rows, intercepts)
+                })
+            }
+
+            // fails with `EstimatorError::Unsupported` if the backend is not supported
+            // on this machine, or has no 16-bit implementation
+            pub fn create_with_backend(
+                backend: Backend,
+                rows: &[Vec<f32>],
+                intercepts: &[f32],
+            ) -> Result<Self, EstimatorError> {
+                let inner = match backend {
+                    #[cfg(target_arch = "x86_64")]
+                    Backend::Avx => InnerHalf::Avx(matrix_avx_f16::MatrixHalf::create_from_rows(
+                        $format, rows, intercepts,
+                    )?),
+                    #[cfg(target_arch = "aarch64")]
+                    Backend::Neon => InnerHalf::Neon(
+                        matrix_arm_f16::MatrixHalf::create_from_rows($format, rows, intercepts)?,
+                    ),
+                    Backend::Scalar => InnerHalf::Scalar(
+                        matrix_scalar_f16::MatrixHalf::create_from_rows($format, rows, intercepts)?,
+                    ),
+                    #[allow(unreachable_patterns)]
+                    _ => return Err(EstimatorError::Unsupported(backend)),
+                };
+
+                let (num_columns, num_rows) =
+                    with_inner_half!(&inner, m => (m.num_columns, m.num_rows));
+
+                Ok(Self {
+                    num_columns,
+                    num_rows,
+                    inner,
+                })
+            }
+
+            pub fn backend(&self) -> Backend {
+                match &self.inner {
+                    #[cfg(target_arch = "x86_64")]
+                    InnerHalf::Avx(_) => Backend::Avx,
+                    #[cfg(target_arch = "aarch64")]
+                    InnerHalf::Neon(_) => Backend::Neon,
+                    InnerHalf::Scalar(_) => Backend::Scalar,
+                }
+            }
+
+            pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+                with_inner_half!(&self.inner, m => m.product(values, destination))
+            }
+
+            pub fn product_softmax_cumulative_approx(
+                &self,
+                values: &[f32],
+                destination: &mut [f32],
+            ) -> Result<(), EstimatorError> {
+                with_inner_half!(
+                    &self.inner,
+                    m => m.product_softmax_cumulative_approx(values, destination)
+                )
+            }
+        }
+    };
+}
+
+// IEEE half precision: about 3 significant digits, magnitudes up to 65504
+half_matrix!(MatrixF16, HalfFormat::F16);
+
+// bfloat16: the range of f32, with about 2 significant digits
+half_matrix!(MatrixBF16, HalfFormat::BF16);
@@ -0,0 +1,100 @@
+use crate::error::{check_intercepts, EstimatorError};
+use crate::exp_approx::exp_approx_f32;
+use crate::packed::Coefficients;
+
+// matrix of f32 with its dimensions fixed at compile time: `IN` inputs (rows) and
+// `OUT` outputs (columns). The coefficients are stored inline, one `[f32; OUT]` per
+// input, so there's no heap allocation, and the inputs and outputs are arrays, so
+// there are no length checks. With the loop bounds known, the compiler unrolls the
+// kernels completely and vectorises across the outputs for small models.
+//
+// being inline, a large model is a large value: box it (or make it a `static`) if
+// it's more than a few thousand coefficients.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedMatrixF32<const IN: usize, const OUT: usize> {
+    coefficients: [[f32; OUT]; IN],
+    intercepts: [f32; OUT],
+}
+
+impl<const IN: usize, const OUT: usize> FixedMatrixF32<IN, OUT> {
+    // one array per input, holding one coefficient per output; `const`, so a model
+    // can be compiled in as a `static`
+    pub const fn new(coefficients: [[f32; OUT]; IN], intercepts: [f32; OUT]) -> Self {
+        Self {
+            coefficients,
+            intercepts,
+        }
+    }
+
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    // the dimensions of `source` must be exactly `IN` x `OUT`
+    pub fn create_from_coefficients(
+        source: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let actual = source.dimensions()?;
+        if actual != (IN, OUT) {
+            return Err(EstimatorError::Shape {
+                expected: (IN, OUT),
+                actual,
+            });
+        }
+        check_intercepts(intercepts.len(), OUT)?;
+
+        let mut matrix = Self::new([[0f32; OUT]; IN], [0f32; OUT]);
+        for (input, row) in matrix.coefficients.iter_mut().enumerate() {
+            for (output, c) in row.iter_mut().enumerate() {
+                *c = source.get(input, output);
+            }
+        }
+        matrix.intercepts.copy_from_slice(intercepts);
+        Ok(matrix)
+    }
+
+    pub fn coefficients(&self) -> &[[f32; OUT]; IN] {
+        &self.coefficients
+    }
+
+    pub fn intercepts(&self) -> &[f32; OUT] {
+        &self.intercepts
+    }
+
+    #[inline(always)]
+    fn linear(&self, values: &[f32; IN]) -> [f32; OUT] {
+        let mut accumulate = self.intercepts;
+        for (val, row) in values.iter().zip(&self.coefficients) {
+            for (acc, c) in accumulate.iter_mut().zip(row) {
+                *acc += c * val;
+            }
+        }
+        accumulate
+    }
+
+    // y = x * [coeff] + [intercepts]
+    #[inline]
+    pub fn product(&self, values: &[f32; IN], destination: &mut [f32; OUT]) {
+        *destination = self.linear(values);
+    }
+
+    // cumulative sum of the approximate exponential of the above (not normalised)
+    #[inline]
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32; IN],
+        destination: &mut [f32; OUT],
+    ) {
+        let mut accumulate = self.linear(values);
+        // the exponentials are independent, so this vectorises; only the sum is serial
+        for acc in accumulate.iter_mut() {
+            *acc = exp_approx_f32(*acc);
+        }
+        let mut cumulative_sum = 0f32;
+        for (d, acc) in destination.iter_mut().zip(&accumulate) {
+            cumulative_sum += acc;
+            *d = cumulative_sum;
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::batch;
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx::exp_approx_f32;
+use crate::packed::{self, Coefficients, KernelF32, PackedColumns};
+use crate::sparse;
+
+pub const SINGLES_PER_INTRINSIC: usize = 4;
+
+// a plain array standing in for an intrinsic; the compiler is free to
+// vectorise operations on it where the target allows
+type Lanes = [f32; SINGLES_PER_INTRINSIC];
+
+// matrix of f32, using the same layout as the SIMD implementations: we split
+// the supplied rows into columns of 4 x 32-bit floats, and then do a
+// column-wise multiplication. Portable, so it builds on every target.
+pub struct MatrixF32 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<Lanes>,
+}
+
+pub fn zeros() -> Lanes {
+    [0f32; SINGLES_PER_INTRINSIC]
+}
+
+// the softmax output step for one column chunk: the approximate exponential of each
+// lane is added to the running sum, which is written to `dst`. `dst` may be shorter
+// than `lanes` for the final chunk. Used by the scalar matrices that don't share the
+// `packed` kernels: `matrix_sparse`, and the f16 and i8 ones.
+#[inline(always)]
+pub(crate) fn cumulative_exp(dst: &mut [f32], lanes: &[f32], cumulative_sum: &mut f32) {
+    dst.iter_mut().zip(lanes).for_each(|(d, s)| {
+        *cumulative_sum += exp_approx_f32(*s);
+        *d = *cumulative_sum;
+    });
+}
+
+// plain loops for the kernels in `packed`; nothing here needs a target feature
+impl KernelF32 for Lanes {
+    type Value = f32;
+
+    #[inline(always)]
+    unsafe fn broadcast(value: f32) -> f32 {
+        value
+    }
+
+    #[inline(always)]
+    unsafe fn multiply_add(accumulate: &mut Lanes, row: Lanes, value: f32) {
+        accumulate
+            .iter_mut()
+            .zip(row)
+            .for_each(|(acc, v)| *acc += v * value);
+    }
+
+    #[inline(always)]
+    unsafe fn exp(v: Lanes) -> Lanes {
+        v.map(exp_approx_f32)
+    }
+
+    #[inline(always)]
+    unsafe fn load(src: &[f32]) -> Lanes {
+        let mut lanes = [0f32; SINGLES_PER_INTRINSIC];
+        lanes[..src.len()].copy_from_slice(src);
+        lanes
+    }
+
+    #[inline(always)]
+    unsafe fn store(dst: &mut [f32], v: Lanes) {
+        dst.copy_from_slice(&v[..dst.len()]);
+    }
+}
+
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    packed::impl_updates!(f32);
+    packed::impl_read_back!(f32);
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: the scalar kernels need no target feature
+        unsafe { self.columns.product(values, destination, false) };
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: the scalar kernels need no target feature
+        unsafe { self.columns.product(values, destination, true) };
+        Ok(())
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            output_indices.len(),
+        )?;
+
+        // safe: the scalar kernels need no target feature
+        unsafe {
+            self.columns
+                .product_selected(values, output_indices, destination)
+        }
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        let mut destination = [0f32];
+        self.product_selected(values, &[output], &mut destination)?;
+        Ok(destination[0])
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one
+    // value per output, as `Scorer` does when an input changes
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        // safe: the scalar kernels need no target feature
+        unsafe { self.columns.add_scaled_row(input, delta, destination) }
+    }
+
+    // `inputs` holds `n_rows` input vectors one after the other, and `outputs`
+    // receives `n_rows` output vectors in the same way
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: the scalar kernels need no target feature
+        unsafe { self.columns.product_batch(inputs, outputs, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: the scalar kernels need no target feature
+        unsafe { self.columns.product_batch(inputs, outputs, true) };
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; everything else is skipped
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: the scalar kernels need no target feature, and the indices were checked above
+        unsafe {
+            self.columns
+                .product_sparse(indices, values, destination, false)
+        };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: the scalar kernels need no target feature, and the indices were checked above
+        unsafe {
+            self.columns
+                .product_sparse(indices, values, destination, true)
+        };
+        Ok(())
+    }
+}
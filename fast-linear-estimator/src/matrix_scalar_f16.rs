@@ -0,0 +1,83 @@
+use crate::error::{check_lengths, EstimatorError};
+use crate::half::{HalfColumns, HalfFormat, Lanes, HALVES_PER_CHUNK};
+use crate::matrix_scalar::cumulative_exp;
+
+// matrix of 16-bit (f16 or bf16) coefficients, see `half`. Portable, so it builds
+// on every target; each coefficient is converted to f32 in software as it's used.
+pub struct MatrixHalf {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: HalfColumns,
+}
+
+impl MatrixHalf {
+    pub fn create_from_rows(
+        format: HalfFormat,
+        rows: &[Vec<f32>],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        let columns = HalfColumns::from_rows(format, rows, intercepts)?;
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    // the product for each chunk in turn, with the destination slice it belongs in
+    #[inline(always)]
+    fn for_each_chunk(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+        mut f: impl FnMut(&mut [f32], &Lanes),
+    ) {
+        let format = self.columns.format;
+        destination
+            .chunks_mut(HALVES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                let mut accumulate = *intercepts;
+                for (val, h) in values.iter().zip(col) {
+                    accumulate
+                        .iter_mut()
+                        .zip(h)
+                        .for_each(|(acc, h)| *acc += val * format.to_f32(*h));
+                }
+                f(dst, &accumulate);
+            });
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            dst.copy_from_slice(&accumulate[..dst.len()])
+        });
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        let mut cumulative_sum = 0f32;
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            cumulative_exp(dst, accumulate, &mut cumulative_sum)
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx::exp_approx_f64;
+use crate::packed::{Coefficients, PackedColumns};
+
+pub const DOUBLES_PER_INTRINSIC: usize = 4;
+
+// a plain array standing in for an intrinsic; the compiler is free to
+// vectorise operations on it where the target allows
+type Lanes = [f64; DOUBLES_PER_INTRINSIC];
+
+// matrix of f64, using the same layout as the SIMD implementations: we split
+// the supplied rows into columns of 4 x 64-bit floats, and then do a
+// column-wise multiplication. Portable, so it builds on every target.
+pub struct MatrixF64 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<Lanes>,
+}
+
+pub fn zeros() -> Lanes {
+    [0f64; DOUBLES_PER_INTRINSIC]
+}
+
+impl MatrixF64 {
+    pub fn create_from_rows(rows: &[Vec<f64>], intercepts: &[f64]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f64>,
+        intercepts: &[f64],
+    ) -> Result<Self, EstimatorError> {
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    #[inline(always)]
+    fn multiply_add(accumulate: &mut Lanes, v1: &Lanes, v2: f64) {
+        accumulate
+            .iter_mut()
+            .zip(v1)
+            .for_each(|(acc, v)| *acc += v * v2);
+    }
+
+    pub fn product(&self, values: &[f64], destination: &mut [f64]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, row_intrin, *val);
+                }
+                // copy to destination -- and we might have a shorter final slice
+                dst.copy_from_slice(&accumulate[0..dst.len()]);
+            });
+
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f64],
+        destination: &mut [f64],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        let mut cumulative_sum = 0f64;
+
+        destination
+            .chunks_mut(DOUBLES_PER_INTRINSIC)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (intercepts, col))| {
+                // run multiplication and add to `accumulate`, starting with the intercepts
+                let mut accumulate = *intercepts;
+                for (val, row_intrin) in values.iter().zip(col) {
+                    Self::multiply_add(&mut accumulate, row_intrin, *val);
+                }
+
+                // copy to destination (taking into account final shorter stub) and apply cumulative softmax
+                // 1. approximate exponential, 2. accumulate and copy
+                dst.iter_mut().zip(accumulate.iter()).for_each(|(d, s)| {
+                    cumulative_sum += exp_approx_f64(*s);
+                    *d = cumulative_sum;
+                });
+            });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_scalar::cumulative_exp;
+use crate::quantize::{Lanes, QuantizedColumns, BYTES_PER_CHUNK};
+
+// matrix of int8 quantized coefficients, see `quantize`. Portable, so it builds
+// on every target; the arithmetic is all in f32.
+pub struct MatrixI8 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    columns: QuantizedColumns,
+}
+
+impl MatrixI8 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_quantized(QuantizedColumns::from_rows(rows, intercepts)?)
+    }
+
+    pub fn create_from_quantized(columns: QuantizedColumns) -> Result<Self, EstimatorError> {
+        Ok(Self {
+            num_columns: columns.num_columns,
+            num_rows: columns.num_rows,
+            columns,
+        })
+    }
+
+    // the product for each chunk in turn, with the destination slice it belongs in
+    #[inline(always)]
+    fn for_each_chunk(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+        mut f: impl FnMut(&mut [f32], &Lanes),
+    ) {
+        let sum: f32 = values.iter().sum();
+
+        destination
+            .chunks_mut(BYTES_PER_CHUNK)
+            .zip(self.columns.chunks())
+            .for_each(|(dst, (col, scales, zero_points, intercepts))| {
+                let mut accumulate = [0f32; BYTES_PER_CHUNK];
+                for (val, q) in values.iter().zip(col) {
+                    accumulate
+                        .iter_mut()
+                        .zip(q)
+                        .for_each(|(acc, q)| *acc += val * *q as f32);
+                }
+                // scale once for the whole column
+                for (((acc, s), z), i) in accumulate
+                    .iter_mut()
+                    .zip(scales)
+                    .zip(zero_points)
+                    .zip(intercepts)
+                {
+                    *acc = i + s * *acc - z * sum;
+                }
+                f(dst, &accumulate);
+            });
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            dst.copy_from_slice(&accumulate[..dst.len()])
+        });
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        let mut cumulative_sum = 0f32;
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            cumulative_exp(dst, accumulate, &mut cumulative_sum)
+        });
+        Ok(())
+    }
+}
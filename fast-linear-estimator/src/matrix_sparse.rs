@@ -0,0 +1,252 @@
+use crate::error::{check_intercepts, check_lengths, row_dimensions, EstimatorError};
+use crate::matrix_scalar::cumulative_exp;
+
+// outputs per column chunk; wide enough for the compiler to use full AVX
+// registers on the multiply-add where the target allows
+pub const SINGLES_PER_CHUNK: usize = 8;
+
+type Lanes = [f32; SINGLES_PER_CHUNK];
+
+// matrix of f32 for wide models where most coefficients are zero (e.g. from L1
+// regularisation). The outputs are split into chunks of 8 as for the dense
+// matrices, but each chunk only keeps the input rows that have a non-zero
+// coefficient somewhere in it, along with their row index:
+//
+//      chunk c:  rows[chunk_starts[c]..chunk_starts[c + 1]]
+//                coefficients[chunk_starts[c]..chunk_starts[c + 1]]
+//
+// so the product for a chunk is the intercepts plus one multiply-add per stored
+// row, and rows with no coefficients in that chunk are never touched.
+pub struct SparseMatrixF32 {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    num_nonzero: usize,
+    chunk_starts: Vec<usize>,
+    rows: Vec<u32>,
+    coefficients: Vec<Lanes>,
+    intercepts: Vec<Lanes>,
+}
+
+fn check_input(input: u32, num_inputs: usize) -> Result<(), EstimatorError> {
+    if input as usize >= num_inputs {
+        return Err(EstimatorError::InputIndex {
+            index: input as usize,
+            num_inputs,
+        });
+    }
+    Ok(())
+}
+
+fn check_output(output: u32, num_outputs: usize) -> Result<(), EstimatorError> {
+    if output as usize >= num_outputs {
+        return Err(EstimatorError::OutputIndex {
+            index: output as usize,
+            num_outputs,
+        });
+    }
+    Ok(())
+}
+
+impl SparseMatrixF32 {
+    // from (input, output, value) triplets, in any order; repeated entries add up
+    pub fn from_triplets(
+        num_inputs: usize,
+        num_outputs: usize,
+        triplets: &[(u32, u32, f32)],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        for &(input, output, _) in triplets {
+            check_input(input, num_inputs)?;
+            check_output(output, num_outputs)?;
+        }
+
+        let mut sorted = triplets.to_vec();
+        sorted.sort_by_key(|&(i, o, _)| (i, o));
+        Self::from_sorted(num_inputs, num_outputs, sorted.into_iter(), intercepts)
+    }
+
+    // compressed sparse rows, with inputs as the rows: the coefficients for input
+    // `i` are at `row_offsets[i]..row_offsets[i + 1]` in `output_indices` and `values`
+    pub fn from_csr(
+        num_inputs: usize,
+        num_outputs: usize,
+        row_offsets: &[usize],
+        output_indices: &[u32],
+        values: &[f32],
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if output_indices.len() != values.len() {
+            return Err(EstimatorError::SparseLength {
+                indices: output_indices.len(),
+                values: values.len(),
+            });
+        }
+        if row_offsets.len() != num_inputs + 1
+            || row_offsets.first() != Some(&0)
+            || row_offsets.last() != Some(&values.len())
+            || row_offsets.windows(2).any(|w| w[0] > w[1])
+        {
+            return Err(EstimatorError::InvalidOffsets);
+        }
+        for &output in output_indices {
+            check_output(output, num_outputs)?;
+        }
+
+        let entries = row_offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(|(i, w)| (w[0]..w[1]).map(move |k| (i as u32, output_indices[k], values[k])));
+        Self::from_sorted(num_inputs, num_outputs, entries, intercepts)
+    }
+
+    // same arguments as the dense matrices; zero coefficients are dropped
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        let (_, num_outputs) = row_dimensions(rows)?;
+
+        let entries = rows.iter().enumerate().flat_map(|(i, r)| {
+            r.iter()
+                .enumerate()
+                .filter(|(_, v)| **v != 0.0)
+                .map(move |(o, v)| (i as u32, o as u32, *v))
+        });
+        Self::from_sorted(rows.len(), num_outputs, entries, intercepts)
+    }
+
+    // `entries` must be in order of input, and within range
+    fn from_sorted(
+        num_inputs: usize,
+        num_outputs: usize,
+        entries: impl Iterator<Item = (u32, u32, f32)>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if num_inputs == 0 {
+            return Err(EstimatorError::NoInputs);
+        }
+        check_intercepts(intercepts.len(), num_outputs)?;
+
+        // gather the stored rows for each chunk; since the entries come in input
+        // order, a row that's already there is always the last one in its chunk
+        let num_chunks = num_outputs.div_ceil(SINGLES_PER_CHUNK);
+        let mut chunks: Vec<Vec<(u32, Lanes)>> = vec![Vec::new(); num_chunks];
+        for (input, output, value) in entries {
+            let chunk = &mut chunks[output as usize / SINGLES_PER_CHUNK];
+            if chunk.last().map(|(r, _)| *r) != Some(input) {
+                chunk.push((input, [0f32; SINGLES_PER_CHUNK]));
+            }
+            if let Some((_, lanes)) = chunk.last_mut() {
+                lanes[output as usize % SINGLES_PER_CHUNK] += value;
+            }
+        }
+
+        let mut mat = Self {
+            num_columns: num_outputs,
+            num_rows: num_inputs,
+            num_nonzero: 0,
+            chunk_starts: vec![0],
+            rows: Vec::new(),
+            coefficients: Vec::new(),
+            intercepts: intercepts
+                .chunks(SINGLES_PER_CHUNK)
+                .map(|c| {
+                    let mut lanes = [0f32; SINGLES_PER_CHUNK];
+                    lanes[..c.len()].copy_from_slice(c);
+                    lanes
+                })
+                .collect(),
+        };
+
+        for chunk in chunks {
+            for (row, lanes) in chunk {
+                // duplicates may have cancelled out
+                let nonzero = lanes.iter().filter(|v| **v != 0.0).count();
+                if nonzero > 0 {
+                    mat.num_nonzero += nonzero;
+                    mat.rows.push(row);
+                    mat.coefficients.push(lanes);
+                }
+            }
+            mat.chunk_starts.push(mat.rows.len());
+        }
+
+        Ok(mat)
+    }
+
+    // number of non-zero coefficients stored
+    pub fn num_nonzero(&self) -> usize {
+        self.num_nonzero
+    }
+
+    // fraction of the coefficients that are non-zero
+    pub fn density(&self) -> f64 {
+        self.num_nonzero as f64 / (self.num_rows * self.num_columns).max(1) as f64
+    }
+
+    #[inline(always)]
+    fn multiply_add(accumulate: &mut Lanes, v1: &Lanes, v2: f32) {
+        accumulate
+            .iter_mut()
+            .zip(v1)
+            .for_each(|(acc, v)| *acc += v * v2);
+    }
+
+    // the product for each chunk in turn, with the destination slice it belongs in
+    #[inline(always)]
+    fn for_each_chunk(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+        mut f: impl FnMut(&mut [f32], &Lanes),
+    ) {
+        destination
+            .chunks_mut(SINGLES_PER_CHUNK)
+            .zip(self.intercepts.iter())
+            .zip(self.chunk_starts.windows(2))
+            .for_each(|((dst, intercepts), range)| {
+                let mut accumulate = *intercepts;
+                let stored = range[0]..range[1];
+                for (row, coeff) in self.rows[stored.clone()]
+                    .iter()
+                    .zip(&self.coefficients[stored])
+                {
+                    // within bounds: rows are checked against `num_rows` on construction
+                    let val = unsafe { *values.get_unchecked(*row as usize) };
+                    Self::multiply_add(&mut accumulate, coeff, val);
+                }
+                f(dst, &accumulate);
+            });
+    }
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            dst.copy_from_slice(&accumulate[..dst.len()])
+        });
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // same output step as the scalar dense matrix
+        let mut cumulative_sum = 0f32;
+        self.for_each_chunk(values, destination, |dst, accumulate| {
+            cumulative_exp(dst, accumulate, &mut cumulative_sum)
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use std::arch::x86_64::*;
+use std::mem::transmute;
+
+use crate::batch;
+use crate::error::{check_lengths, EstimatorError};
+use crate::matrix_dispatch::Backend;
+use crate::packed::{self, Coefficients, KernelF32, PackedColumns};
+use crate::sparse;
+
+pub const SINGLES_PER_INTRINSIC: usize = 4;
+
+// matrix of f32, but we split the supplied rows into
+// columns of SSE instrinsics (4 x 32-bit floats), and then
+// do a column-wise multiplication
+//
+// same 4-wide layout as `matrix_arm`, for x86 machines without AVX (older Atom
+// and Celeron parts). `_mm_floor_ps` needs SSE4.1, so construction fails if
+// that is not available.
+pub struct MatrixF32 {
+    pub num_columns: usize,
+    pub num_col_instrinsics: usize,
+    pub num_rows: usize,
+    columns: PackedColumns<__m128>,
+}
+
+pub fn zeros() -> __m128 {
+    unsafe { std::mem::transmute([0f32; SINGLES_PER_INTRINSIC]) }
+}
+
+// safe: an intrinsic is exactly `SINGLES_PER_INTRINSIC` f32s
+unsafe impl packed::Lanes for __m128 {
+    type Elem = f32;
+    const LANES: usize = SINGLES_PER_INTRINSIC;
+}
+
+pub fn is_supported() -> bool {
+    is_x86_feature_detected!("sse4.1")
+}
+
+// the intrinsics for the kernels in `packed`
+impl KernelF32 for __m128 {
+    type Value = __m128;
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn broadcast(value: f32) -> __m128 {
+        _mm_set1_ps(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn multiply_add(accumulate: &mut __m128, row: __m128, value: __m128) {
+        // no FMA without AVX2, so separate multiply and add
+        let mult = _mm_mul_ps(value, row);
+        *accumulate = _mm_add_ps(*accumulate, mult);
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn exp(v: __m128) -> __m128 {
+        crate::exp_approx_sse::exp_approx_ssef32(v)
+    }
+
+    // a whole intrinsic directly, or the final shorter stub through an array
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load(src: &[f32]) -> __m128 {
+        if src.len() == SINGLES_PER_INTRINSIC {
+            _mm_loadu_ps(src.as_ptr())
+        } else {
+            let mut lanes = [0f32; SINGLES_PER_INTRINSIC];
+            lanes[..src.len()].copy_from_slice(src);
+            _mm_loadu_ps(lanes.as_ptr())
+        }
+    }
+
+    // a whole intrinsic directly, or the final shorter stub through an array
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn store(dst: &mut [f32], v: __m128) {
+        if dst.len() == SINGLES_PER_INTRINSIC {
+            _mm_storeu_ps(dst.as_mut_ptr(), v)
+        } else {
+            let src: &[f32; SINGLES_PER_INTRINSIC] = transmute(&v);
+            dst.copy_from_slice(&src[..dst.len()]);
+        }
+    }
+}
+
+impl MatrixF32 {
+    pub fn create_from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn create_from_coefficients(
+        coefficients: Coefficients<f32>,
+        intercepts: &[f32],
+    ) -> Result<Self, EstimatorError> {
+        if !is_supported() {
+            return Err(EstimatorError::Unsupported(Backend::Sse));
+        }
+
+        let columns = PackedColumns::from_coefficients(coefficients, intercepts)?;
+
+        Ok(Self {
+            num_columns: columns.num_outputs(),
+            num_col_instrinsics: columns.num_chunks(),
+            num_rows: columns.num_rows(),
+            columns,
+        })
+    }
+
+    packed::impl_updates!(f32);
+    packed::impl_read_back!(f32);
+
+    pub fn product(&self, values: &[f32], destination: &mut [f32]) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.product_sse(values, destination, false) };
+        Ok(())
+    }
+
+    pub fn product_softmax_cumulative_approx(
+        &self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.product_sse(values, destination, true) };
+        Ok(())
+    }
+
+    // only the outputs at `output_indices`, in that order, computing just the column
+    // chunks that cover them
+    pub fn product_selected(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_rows,
+            output_indices.len(),
+        )?;
+
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.selected_sse(values, output_indices, destination) }
+    }
+
+    // the linear predictor for a single output
+    pub fn product_output(&self, values: &[f32], output: usize) -> Result<f32, EstimatorError> {
+        let mut destination = [0f32];
+        self.product_selected(values, &[output], &mut destination)?;
+        Ok(destination[0])
+    }
+
+    // add `delta` times an input's coefficients to `destination`, which has one
+    // value per output, as `Scorer` does when an input changes
+    pub fn add_scaled_input_row(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.scaled_row_sse(input, delta, destination) }
+    }
+
+    // `inputs` holds `n_rows` input vectors one after the other, and `outputs`
+    // receives `n_rows` output vectors in the same way
+    pub fn product_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.batch_sse(inputs, outputs, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_batch(
+        &self,
+        inputs: &[f32],
+        n_rows: usize,
+        outputs: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        batch::check_dimensions(inputs, n_rows, outputs, self.num_rows, self.num_columns)?;
+
+        // safe: SSE4.1 support was checked on construction
+        unsafe { self.batch_sse(inputs, outputs, true) };
+        Ok(())
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; everything else is skipped
+    pub fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: SSE4.1 support was checked on construction, and the indices above
+        unsafe { self.sparse_sse(indices, values, destination, false) };
+        Ok(())
+    }
+
+    pub fn softmax_cumulative_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        sparse::check_inputs(
+            indices,
+            values,
+            destination.len(),
+            self.num_rows,
+            self.num_columns,
+        )?;
+
+        // safe: SSE4.1 support was checked on construction, and the indices above
+        unsafe { self.sparse_sse(indices, values, destination, true) };
+        Ok(())
+    }
+
+    // the kernels in `packed`, compiled for SSE4.1 so the intrinsics above are inlined
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn product_sse(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        self.columns.product(values, destination, softmax)
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn selected_sse(
+        &self,
+        values: &[f32],
+        output_indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns
+            .product_selected(values, output_indices, destination)
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn scaled_row_sse(
+        &self,
+        input: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.columns.add_scaled_row(input, delta, destination)
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn batch_sse(&self, inputs: &[f32], outputs: &mut [f32], softmax: bool) {
+        self.columns.product_batch(inputs, outputs, softmax)
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn sparse_sse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+    ) {
+        self.columns
+            .product_sparse(indices, values, destination, softmax)
+    }
+}
@@ -4,6 +4,9 @@ mod tests {
 
     use approx::abs_diff_eq;
 
+    use crate::error::EstimatorError;
+    use crate::estimator::{available_estimators, default_estimator};
+
     #[cfg(target_arch = "x86_64")]
     use crate::matrix_avx::MatrixF32;
     #[cfg(target_arch = "x86_64")]
@@ -14,6 +17,40 @@ mod tests {
     #[cfg(target_arch = "aarch64")]
     use crate::matrix_arm::SINGLES_PER_INTRINSIC;
 
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    use crate::matrix_scalar::MatrixF32;
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    use crate::matrix_scalar::SINGLES_PER_INTRINSIC;
+
+    use crate::matrix_dispatch::Backend;
+
+    // `num_outputs` coefficients for each of `num_inputs` inputs, a different mix of
+    // positive and negative values for each, so a wrong input or output shows
+    fn fixture_rows(num_inputs: usize, num_outputs: usize) -> Vec<Vec<f32>> {
+        (0..num_inputs)
+            .map(|r| {
+                (0..num_outputs)
+                    .map(|c| ((r * 7 + c * 3) % 11) as f32 * 0.1 - 0.5)
+                    .collect()
+            })
+            .collect()
+    }
+
+    // each of the dispatched f32 backends that this CPU can run, the scalar one last
+    fn for_each_supported_backend(mut f: impl FnMut(Backend)) {
+        for &backend in &[
+            Backend::Avx512,
+            Backend::Avx,
+            Backend::Sse,
+            Backend::Neon,
+            Backend::Scalar,
+        ] {
+            if backend.is_supported() {
+                f(backend);
+            }
+        }
+    }
+
     #[test]
     fn structure_create_exact() {
         // 5 rows, 10 columns
@@ -51,11 +88,12 @@ mod tests {
         //
         let rows = vec![vec![1.0f32, 2.0, 3.0], vec![4.0f32, 5.0, 6.0]];
         let intercepts = [10f32, 20f32, 30f32];
-        let matrix = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
-        let v = vec![1f32, 2.];
-        let mut res = vec![0f32; 3];
-        matrix.product(&v, &mut res);
-        assert_eq!(res, [19f32, 32., 45.]);
+        for matrix in available_estimators(&rows, &intercepts) {
+            let v = vec![1f32, 2.];
+            let mut res = vec![0f32; 3];
+            matrix.product(&v, &mut res).unwrap();
+            assert_eq!(res, [19f32, 32., 45.]);
+        }
     }
 
     #[test]
@@ -64,18 +102,20 @@ mod tests {
         let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
         let intercepts = [0f32; 35]; // leave these zero; another test covers this
 
-        let matrix = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
-        let v: Vec<f32> = (1..=5).map(|x| x as f32).collect();
+        for matrix in available_estimators(&rows, &intercepts) {
+            let v: Vec<f32> = (1..=5).map(|x| x as f32).collect();
 
-        // output to f32
-        let mut res = vec![0f32; 35];
-        matrix.product(&v, &mut res);
-        assert_eq!(res[0], 1415_f32);
-        assert_eq!(res[18], 1685_f32);
-        assert_eq!(res[34], 1925_f32);
+            // output to f32
+            let mut res = vec![0f32; 35];
+            matrix.product(&v, &mut res).unwrap();
+            assert_eq!(res[0], 1415_f32);
+            assert_eq!(res[18], 1685_f32);
+            assert_eq!(res[34], 1925_f32);
+        }
     }
 
     #[test]
+    #[allow(clippy::excessive_precision)] // values as printed by R
     fn product_softmax() {
         // in R,
         //      > coeff = t(matrix(1:6, ncol=2))
@@ -87,16 +127,1439 @@ mod tests {
         //
         let rows = vec![vec![1.0f32, 2.0, 3.0], vec![4.0f32, 5.0, 6.0]];
         let intercepts = [0.1f32, 0.2f32, 0.3f32];
-        let matrix = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
-        let v = vec![0.1f32, 0.5f32];
+        for matrix in available_estimators(&rows, &intercepts) {
+            let v = vec![0.1f32, 0.5f32];
+            let mut res = vec![0f32; 3];
+            matrix
+                .product_softmax_cumulative_approx(&v, &mut res)
+                .unwrap();
+
+            // check approximately equal (with faily large tolerance since the numbers are large)
+            let ok = res
+                .iter()
+                .zip(&[9.025013_f32, 27.199159_f32, 63.797393_f32])
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 0.01f32));
+            assert!(ok);
+
+            // normalised probabilities
+            matrix.product_softmax_approx(&v, &mut res).unwrap();
+            let ok = res
+                .iter()
+                .zip(&[0.141464_f32, 0.284873_f32, 0.573663_f32])
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32));
+            assert!(ok);
+        }
+    }
+
+    #[test]
+    fn wrong_dimensions() {
+        let rows = vec![vec![1.0f32, 2.0, 3.0], vec![4.0f32, 5.0, 6.0]];
+        let intercepts = [10f32, 20f32, 30f32];
+        for matrix in available_estimators(&rows, &intercepts) {
+            assert_eq!(matrix.num_inputs(), 2);
+            assert_eq!(matrix.num_outputs(), 3);
+
+            let mut res = vec![0f32; 3];
+            assert_eq!(
+                matrix.product(&[1f32], &mut res),
+                Err(EstimatorError::InputLength {
+                    expected: 2,
+                    actual: 1
+                })
+            );
+            assert_eq!(
+                matrix.product(&[1f32, 2.], &mut res[..2]),
+                Err(EstimatorError::OutputLength {
+                    expected: 3,
+                    actual: 2
+                })
+            );
+            assert!(matrix
+                .product_softmax_cumulative_approx(&[1f32, 2., 3.], &mut res)
+                .is_err());
+        }
+        assert!(available_estimators(&rows, &intercepts[..2]).is_empty());
+        assert!(default_estimator(&rows, &intercepts).is_ok());
+    }
+
+    #[test]
+    fn non_finite() {
+        use crate::matrix_dispatch::MatrixF32;
+        use crate::packed::Coefficients;
+        use crate::LinearEstimator;
+
+        let mut rows = vec![vec![1.0f32, 2.0, 3.0], vec![4.0f32, 5.0, 6.0]];
+        let mut intercepts = vec![10f32, 20f32, 30f32];
+        let matrix = MatrixF32::create_validated(Coefficients::Rows(&rows), &intercepts).unwrap();
+
+        // checked products pass finite inputs through unchanged
         let mut res = vec![0f32; 3];
-        matrix.product_softmax_cumulative_approx(&v, &mut res);
+        let mut checked = vec![0f32; 3];
+        matrix.product(&[0.5, -1.0], &mut res).unwrap();
+        matrix.product_checked(&[0.5, -1.0], &mut checked).unwrap();
+        assert_eq!(res, checked);
+        matrix
+            .product_softmax_cumulative_approx(&[0.01, 0.02], &mut res)
+            .unwrap();
+        matrix
+            .softmax_cumulative_checked(&[0.01, 0.02], &mut checked)
+            .unwrap();
+        assert_eq!(res, checked);
+
+        // and report every input that isn't finite
+        assert_eq!(
+            matrix.product_checked(&[f32::NAN, f32::INFINITY], &mut res),
+            Err(EstimatorError::NonFiniteInputs(vec![0, 1]))
+        );
+        assert_eq!(
+            matrix.softmax_cumulative_checked(&[0.0, f32::NEG_INFINITY], &mut res),
+            Err(EstimatorError::NonFiniteInputs(vec![1]))
+        );
+
+        // validation reports where the first bad coefficient or intercept is
+        intercepts[2] = f32::INFINITY;
+        assert_eq!(
+            MatrixF32::create_validated(Coefficients::Rows(&rows), &intercepts).err(),
+            Some(EstimatorError::NonFiniteIntercept { output: 2 })
+        );
+        rows[1][1] = f32::NAN;
+        assert_eq!(
+            MatrixF32::create_validated(Coefficients::Rows(&rows), &intercepts).err(),
+            Some(EstimatorError::NonFiniteCoefficient {
+                input: 1,
+                output: 1
+            })
+        );
+        let column_major: Vec<f64> = vec![1.0, f64::NAN, 3.0, 4.0, 5.0, 6.0];
+        let source = Coefficients::ColumnMajor {
+            data: &column_major,
+            num_inputs: 2,
+            num_outputs: 3,
+        };
+        assert_eq!(
+            crate::MatrixF64::create_validated(source, &[0f64; 3]).err(),
+            Some(EstimatorError::NonFiniteCoefficient {
+                input: 1,
+                output: 0
+            })
+        );
+        // without validation, they're accepted as before
+        assert!(MatrixF32::create_from_rows(&rows, &intercepts).is_ok());
+    }
+
+    #[test]
+    fn fixed_size() {
+        use crate::{FixedMatrixF32, LinearEstimator};
 
-        // check approximately equal (with faily large tolerance since the numbers are large)
-        let ok = res
+        // compiled in, no heap
+        static SMALL: FixedMatrixF32<2, 3> =
+            FixedMatrixF32::new([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]], [10.0, 20.0, 30.0]);
+        let mut res = [0f32; 3];
+        SMALL.product(&[2.0, 3.0], &mut res);
+        assert_eq!(res, [24.0, 39.0, 54.0]);
+
+        // the same as the dynamic matrix
+        let rows: Vec<Vec<f32>> = (0..12)
+            .map(|i| {
+                (0..9)
+                    .map(|o| ((i * 9 + o) as f32 * 0.37).sin() * 0.1)
+                    .collect()
+            })
+            .collect();
+        let intercepts: Vec<f32> = (0..9).map(|o| o as f32 * 0.01).collect();
+        let fixed = FixedMatrixF32::<12, 9>::create_from_rows(&rows, &intercepts).unwrap();
+        let dynamic = crate::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        assert_eq!(fixed.intercepts()[..], intercepts[..]);
+        assert_eq!(fixed.coefficients()[11][..], rows[11][..]);
+
+        let mut values = [0f32; 12];
+        for (i, v) in values.iter_mut().enumerate() {
+            *v = (i as f32 - 6.0) * 0.4;
+        }
+        let mut expected = vec![0f32; 9];
+        let mut actual = [0f32; 9];
+        dynamic.product(&values, &mut expected).unwrap();
+        fixed.product(&values, &mut actual);
+        assert!(actual
             .iter()
-            .zip(&[9.025013_f32, 27.199159_f32, 63.797393_f32])
-            .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 0.01f32));
-        assert!(ok);
+            .zip(&expected)
+            .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-5f32)));
+        dynamic
+            .product_softmax_cumulative_approx(&values, &mut expected)
+            .unwrap();
+        fixed.product_softmax_cumulative_approx(&values, &mut actual);
+        assert!(actual
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32)));
+
+        // through the trait, with slices
+        let estimator: &dyn LinearEstimator = &fixed;
+        let mut through_trait = vec![0f32; 9];
+        estimator
+            .product_softmax_cumulative_approx(&values, &mut through_trait)
+            .unwrap();
+        assert_eq!(through_trait, actual);
+        assert_eq!(
+            estimator.product(&values[..11], &mut through_trait),
+            Err(EstimatorError::InputLength {
+                expected: 12,
+                actual: 11
+            })
+        );
+
+        // the rows have to be exactly the right shape
+        assert_eq!(
+            FixedMatrixF32::<12, 8>::create_from_rows(&rows, &intercepts[..8]).err(),
+            Some(EstimatorError::Shape {
+                expected: (12, 8),
+                actual: (12, 9)
+            })
+        );
+        assert!(FixedMatrixF32::<12, 9>::create_from_rows(&rows, &intercepts[..8]).is_err());
+    }
+
+    #[test]
+    fn construction_errors() {
+        use crate::{MatrixF16, MatrixI8, SparseMatrixF32};
+
+        let rows = vec![vec![1.0f32, 2.0, 3.0], vec![4.0f32, 5.0, 6.0]];
+        let intercepts = [10f32, 20f32, 30f32];
+
+        assert_eq!(
+            default_estimator(&[], &intercepts).err(),
+            Some(EstimatorError::NoInputs)
+        );
+        let ragged = vec![rows[0].clone(), rows[1].clone(), vec![1.0f32]];
+        let error = default_estimator(&ragged, &intercepts).err().unwrap();
+        assert_eq!(
+            error,
+            EstimatorError::RaggedRow {
+                row: 2,
+                expected: 3,
+                actual: 1
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "row 2 has 1 coefficients, but the first row has 3"
+        );
+        assert_eq!(
+            default_estimator(&rows, &intercepts[..2]).err(),
+            Some(EstimatorError::InterceptLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        // the same errors from every kind of matrix
+        assert_eq!(
+            MatrixI8::create_from_rows(&ragged, &intercepts).err(),
+            default_estimator(&ragged, &intercepts).err()
+        );
+        assert_eq!(
+            MatrixF16::create_from_rows(&ragged, &intercepts).err(),
+            default_estimator(&ragged, &intercepts).err()
+        );
+        assert_eq!(
+            SparseMatrixF32::create_from_rows(&ragged, &intercepts).err(),
+            default_estimator(&ragged, &intercepts).err()
+        );
+    }
+
+    #[test]
+    fn dispatch_backends_agree() {
+        use crate::matrix_dispatch::{self, Backend};
+
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+        let v: Vec<f32> = (1..=5).map(|x| x as f32).collect();
+
+        // the detected backend is always usable
+        let detected = matrix_dispatch::detect_backend();
+        assert!(detected.is_supported());
+        let matrix = matrix_dispatch::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        assert_eq!(matrix.backend(), detected);
+
+        let scalar =
+            matrix_dispatch::MatrixF32::create_with_backend(Backend::Scalar, &rows, &intercepts)
+                .unwrap();
+        let mut expected = vec![0f32; 35];
+        let mut expected_softmax = vec![0f32; 35];
+        scalar.product(&v, &mut expected).unwrap();
+        scalar
+            .product_softmax_cumulative_approx(&v, &mut expected_softmax)
+            .unwrap();
+
+        for &backend in &[Backend::Avx512, Backend::Avx, Backend::Sse, Backend::Neon] {
+            let created =
+                matrix_dispatch::MatrixF32::create_with_backend(backend, &rows, &intercepts);
+            // unsupported backends refuse to construct rather than fault later
+            assert_eq!(created.is_ok(), backend.is_supported());
+            if let Ok(matrix) = created {
+                let mut res = vec![0f32; 35];
+                matrix.product(&v, &mut res).unwrap();
+                assert!(res
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32)));
+
+                matrix
+                    .product_softmax_cumulative_approx(&v, &mut res)
+                    .unwrap();
+                assert!(res
+                    .iter()
+                    .zip(&expected_softmax)
+                    .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-3f32)));
+            }
+        }
+    }
+
+    #[test]
+    fn tiled_products() {
+        use crate::matrix_dispatch::MatrixF32;
+
+        // enough inputs for tiles and not, over widths from a single column chunk
+        // to two tiles and some left over, on every backend's chunk size
+        for &num_inputs in &[4, 16, 40] {
+            for &num_outputs in &[3, 8, 17, 33, 64, 100, 150] {
+                let rows = fixture_rows(num_inputs, num_outputs);
+                let intercepts: Vec<f32> = (0..num_outputs).map(|c| c as f32 * -0.001).collect();
+                let v: Vec<f32> = (0..num_inputs)
+                    .map(|r| (r % 5) as f32 * 0.5 - 1.0)
+                    .collect();
+
+                let expected: Vec<f32> = (0..num_outputs)
+                    .map(|c| {
+                        intercepts[c] + (0..num_inputs).map(|r| rows[r][c] * v[r]).sum::<f32>()
+                    })
+                    .collect();
+                let mut expected_softmax = vec![0f32; num_outputs];
+                MatrixF32::create_with_backend(Backend::Scalar, &rows, &intercepts)
+                    .unwrap()
+                    .product_softmax_cumulative_approx(&v, &mut expected_softmax)
+                    .unwrap();
+
+                for_each_supported_backend(|backend| {
+                    let matrix =
+                        MatrixF32::create_with_backend(backend, &rows, &intercepts).unwrap();
+                    let mut res = vec![0f32; num_outputs];
+                    matrix.product(&v, &mut res).unwrap();
+                    assert!(res.iter().zip(&expected).all(|(a, b)| abs_diff_eq!(
+                        a,
+                        b,
+                        epsilon = 1e-4f32 * b.abs().max(1.0)
+                    )));
+
+                    matrix
+                        .product_softmax_cumulative_approx(&v, &mut res)
+                        .unwrap();
+                    assert!(res.iter().zip(&expected_softmax).all(|(a, b)| abs_diff_eq!(
+                        a,
+                        b,
+                        epsilon = 1e-3f32 * b.abs().max(1.0)
+                    )));
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn product_selected() {
+        use crate::matrix_dispatch::MatrixF32;
+
+        // 40 inputs (so wide enough shapes are tiled), 150 outputs
+        let rows = fixture_rows(40, 150);
+        let intercepts: Vec<f32> = (0..150).map(|c| c as f32 * -0.001).collect();
+        let v: Vec<f32> = (0..40).map(|r| (r % 5) as f32 * 0.5 - 1.0).collect();
+        // unsorted, repeated, and at both ends
+        let indices = [0, 149, 3, 3, 17, 8, 64, 63, 149];
+
+        for_each_supported_backend(|backend| {
+            let matrix = MatrixF32::create_with_backend(backend, &rows, &intercepts).unwrap();
+            let mut all = vec![0f32; 150];
+            matrix.product(&v, &mut all).unwrap();
+
+            // the same sums in the same order, so the same results
+            let mut res = vec![0f32; indices.len()];
+            matrix.product_selected(&v, &indices, &mut res).unwrap();
+            let expected: Vec<f32> = indices.iter().map(|i| all[*i]).collect();
+            assert_eq!(res, expected);
+            assert_eq!(matrix.product_output(&v, 77).unwrap(), all[77]);
+
+            matrix.product_selected(&v, &[], &mut []).unwrap();
+            assert_eq!(
+                matrix.product_selected(&v, &[2, 150], &mut res[..2]),
+                Err(EstimatorError::OutputIndex {
+                    index: 150,
+                    num_outputs: 150
+                })
+            );
+            assert_eq!(
+                matrix.product_selected(&v, &indices, &mut res[..2]),
+                Err(EstimatorError::OutputLength {
+                    expected: indices.len(),
+                    actual: 2
+                })
+            );
+            assert!(matrix.product_output(&v[..39], 0).is_err());
+        });
+    }
+
+    #[test]
+    fn incremental_scorer() {
+        use crate::{MatrixF32, Scorer};
+
+        // 30 inputs, 50 outputs
+        let rows = fixture_rows(30, 50);
+        let intercepts: Vec<f32> = (0..50).map(|c| c as f32 * -0.01).collect();
+        let matrix = MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        let mut v: Vec<f32> = (0..30).map(|r| (r % 5) as f32 * 0.5 - 1.0).collect();
+
+        let fresh = |v: &[f32]| {
+            let mut expected = vec![0f32; 50];
+            matrix.product(v, &mut expected).unwrap();
+            expected
+        };
+        let close = |a: &[f32], b: &[f32]| {
+            a.iter()
+                .zip(b)
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32 * b.abs().max(1.0)))
+        };
+
+        // perturb one or two inputs at a time, refreshing every 8 changes or never
+        for &refresh_interval in &[8, 0] {
+            let mut scorer = Scorer::create_with_refresh(&matrix, &v, refresh_interval).unwrap();
+            let mut state = 12345u32;
+            for step in 0..300 {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                let input = (state >> 8) as usize % 30;
+                let value = ((state >> 16) % 1000) as f32 * 0.01 - 5.0;
+                v[input] = value;
+                if step % 3 == 0 {
+                    let other = (input + 7) % 30;
+                    v[other] = -value * 0.5;
+                    scorer
+                        .set_inputs(&[(input, value), (other, -value * 0.5)])
+                        .unwrap();
+                } else {
+                    scorer.set_input(input, value).unwrap();
+                }
+                assert_eq!(scorer.values(), &v[..]);
+                assert!(close(scorer.outputs(), &fresh(&v)));
+            }
+
+            // an explicit refresh gives exactly the fresh product
+            scorer.refresh().unwrap();
+            assert_eq!(scorer.outputs(), &fresh(&v)[..]);
+        }
+
+        // a change through a non-finite value recovers once it's finite again
+        let mut scorer = Scorer::create(&matrix, &v).unwrap();
+        scorer.set_input(4, f32::INFINITY).unwrap();
+        scorer.set_input(4, 1.5).unwrap();
+        v[4] = 1.5;
+        assert!(close(scorer.outputs(), &fresh(&v)));
+
+        // the softmax of the kept outputs matches the matrix's own
+        let mut expected = vec![0f32; 50];
+        let mut res = vec![0f32; 50];
+        matrix
+            .product_softmax_cumulative_approx(&v, &mut expected)
+            .unwrap();
+        scorer.softmax_cumulative_approx(&mut res).unwrap();
+        assert!(res.iter().zip(&expected).all(|(a, b)| abs_diff_eq!(
+            a,
+            b,
+            epsilon = 1e-3f32 * b.abs().max(1.0)
+        )));
+
+        // all new values
+        let w: Vec<f32> = (0..30).map(|r| r as f32 * 0.1).collect();
+        scorer.set_values(&w).unwrap();
+        assert_eq!(scorer.outputs(), &fresh(&w)[..]);
+
+        // each backend's update matches adding the row by hand, the final partial
+        // chunk included
+        for_each_supported_backend(|backend| {
+            let m = MatrixF32::create_with_backend(backend, &rows, &intercepts).unwrap();
+            let mut res = fresh(&w);
+            let mut expected = res.clone();
+            m.add_scaled_input_row(7, 1.5, &mut res).unwrap();
+            for (e, c) in expected.iter_mut().zip(&rows[7]) {
+                *e += c * 1.5;
+            }
+            assert!(close(&res, &expected));
+            assert!(m.add_scaled_input_row(30, 1.5, &mut res).is_err());
+            assert!(m.add_scaled_input_row(7, 1.5, &mut res[..49]).is_err());
+        });
+
+        assert_eq!(
+            scorer.set_input(30, 1.0),
+            Err(EstimatorError::InputIndex {
+                index: 30,
+                num_inputs: 30
+            })
+        );
+        assert!(scorer.set_values(&w[..29]).is_err());
+        assert!(scorer.softmax_cumulative_approx(&mut res[..49]).is_err());
+        assert!(Scorer::create(&matrix, &w[..29]).is_err());
+    }
+
+    #[test]
+    fn update_in_place() {
+        use crate::matrix_dispatch::{Backend, MatrixF32};
+
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+        let v: Vec<f32> = (1..=5).map(|x| x as f32).collect();
+
+        // what the updates below should give
+        let mut updated_rows = rows.clone();
+        updated_rows[2] = (0..35).map(|x| (x as f32) * -0.002).collect();
+        updated_rows[4][34] = 0.5;
+        updated_rows[0][0] = -0.25;
+        let updated_intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * 0.03 + 0.1).collect();
+
+        for &backend in &[
+            Backend::Avx512,
+            Backend::Avx,
+            Backend::Sse,
+            Backend::Neon,
+            Backend::Scalar,
+        ] {
+            let mut matrix = match MatrixF32::create_with_backend(backend, &rows, &intercepts) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let rebuilt =
+                MatrixF32::create_with_backend(backend, &updated_rows, &updated_intercepts)
+                    .unwrap();
+
+            let shifted: Vec<f32> = (0..35).map(|x| (x as f32) * 0.03).collect();
+            matrix.set_intercepts(&shifted).unwrap();
+            matrix.add_to_intercepts(&[0.1f32; 35]).unwrap();
+            matrix.set_input_row(2, &updated_rows[2]).unwrap();
+            matrix.set_coefficient(4, 34, 0.5).unwrap();
+            matrix.set_coefficient(0, 0, -0.25).unwrap();
+
+            let mut res = vec![0f32; 35];
+            let mut expected = vec![0f32; 35];
+            matrix.product(&v, &mut res).unwrap();
+            rebuilt.product(&v, &mut expected).unwrap();
+            assert_eq!(res, expected);
+            matrix
+                .product_softmax_cumulative_approx(&v, &mut res)
+                .unwrap();
+            rebuilt
+                .product_softmax_cumulative_approx(&v, &mut expected)
+                .unwrap();
+            assert_eq!(res, expected);
+
+            // out of range, or the wrong length
+            assert!(matrix.set_intercepts(&[0f32; 34]).is_err());
+            assert!(matrix.add_to_intercepts(&[0f32; 36]).is_err());
+            assert!(matrix.set_input_row(5, &[0f32; 35]).is_err());
+            assert!(matrix.set_coefficient(0, 35, 1.0).is_err());
+        }
+    }
+
+    #[test]
+    fn read_back() {
+        use crate::matrix_dispatch::{Backend, MatrixF32};
+
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+
+        for &backend in &[
+            Backend::Avx512,
+            Backend::Avx,
+            Backend::Sse,
+            Backend::Neon,
+            Backend::Scalar,
+        ] {
+            let mut matrix = match MatrixF32::create_with_backend(backend, &rows, &intercepts) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            // round trip, with the padding dropped
+            assert_eq!(matrix.to_rows(), rows);
+            assert_eq!(matrix.intercepts(), intercepts);
+            assert_eq!(matrix.coefficient(4, 34), Some(rows[4][34]));
+            assert_eq!(matrix.coefficient(5, 0), None);
+            assert_eq!(matrix.coefficient(0, 35), None);
+            let expected = (0..5).flat_map(|i| (0..35).map(move |o| (i, o)));
+            assert!(matrix
+                .coefficients()
+                .eq(expected.map(|(i, o)| (i, o, coeffs[i * 35 + o]))));
+
+            // and it sees the in-place updates
+            matrix.set_coefficient(3, 33, 42.0).unwrap();
+            assert_eq!(matrix.coefficient(3, 33), Some(42.0));
+            assert_eq!(matrix.to_rows()[3][33], 42.0);
+        }
+    }
+
+    #[test]
+    fn flat_constructors() {
+        use crate::{MatrixF32, MatrixF64};
+
+        // 5 inputs, 35 outputs
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let slices: Vec<&[f32]> = rows.iter().map(|r| &r[..]).collect();
+        let column_major: Vec<f32> = (0..35)
+            .flat_map(|o| rows.iter().map(move |r| r[o]))
+            .collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+
+        let matrices = [
+            MatrixF32::create_from_rows(&rows, &intercepts).unwrap(),
+            MatrixF32::from_slices(&slices, &intercepts).unwrap(),
+            MatrixF32::from_row_major(&coeffs, 5, 35, &intercepts).unwrap(),
+            MatrixF32::from_column_major(&column_major, 5, 35, &intercepts).unwrap(),
+        ];
+        for matrix in &matrices {
+            assert_eq!((matrix.num_rows, matrix.num_columns), (5, 35));
+            assert_eq!(matrix.to_rows(), rows);
+            assert_eq!(matrix.intercepts(), intercepts);
+        }
+
+        // wrong length, no inputs, ragged, or the wrong number of intercepts
+        assert!(MatrixF32::from_row_major(&coeffs[1..], 5, 35, &intercepts).is_err());
+        assert!(MatrixF32::from_column_major(&coeffs, 5, 34, &intercepts[..34]).is_err());
+        assert!(MatrixF32::from_row_major(&[], 0, 35, &intercepts).is_err());
+        assert!(MatrixF32::from_slices(&[&coeffs[..35], &coeffs[..34]], &intercepts).is_err());
+        assert!(MatrixF32::from_row_major(&coeffs, 35, 5, &intercepts).is_err());
+
+        let coeffs_f64: Vec<f64> = coeffs.iter().map(|c| *c as f64).collect();
+        let intercepts_f64: Vec<f64> = intercepts.iter().map(|c| *c as f64).collect();
+        let v = [1f64, 2., 3., 4., 5.];
+        let mut expected = vec![0f64; 35];
+        let mut res = vec![0f64; 35];
+        let rows_f64: Vec<Vec<f64>> = coeffs_f64.chunks(35).map(|c| c.to_vec()).collect();
+        MatrixF64::create_from_rows(&rows_f64, &intercepts_f64)
+            .unwrap()
+            .product(&v, &mut expected)
+            .unwrap();
+        MatrixF64::from_row_major(&coeffs_f64, 5, 35, &intercepts_f64)
+            .unwrap()
+            .product(&v, &mut res)
+            .unwrap();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn standardised_inputs() {
+        use crate::matrix_dispatch::MatrixF32;
+        use crate::packed::Coefficients;
+
+        // 6 inputs, 19 outputs
+        let rows = fixture_rows(6, 19);
+        let intercepts: Vec<f32> = (0..19).map(|c| c as f32 * 0.02 - 0.1).collect();
+        let mean = [10f32, -3., 0., 250., 0.5, -0.01];
+        let scale = [2f32, 0.5, 1., 40., 0.1, 0.003];
+        let raw = [12.5f32, -2., 1.5, 190., 0.45, 0.002];
+
+        // explicit standardisation, then the product
+        let standardised: Vec<f32> = raw
+            .iter()
+            .zip(&mean)
+            .zip(&scale)
+            .map(|((x, m), s)| (x - m) / s)
+            .collect();
+        let mut expected = vec![0f32; 19];
+        MatrixF32::create_from_rows(&rows, &intercepts)
+            .unwrap()
+            .product(&standardised, &mut expected)
+            .unwrap();
+
+        for_each_supported_backend(|backend| {
+            let matrix = MatrixF32::create_standardised_with_backend(
+                backend,
+                Coefficients::Rows(&rows),
+                &intercepts,
+                &mean,
+                &scale,
+            )
+            .unwrap();
+            let mut res = vec![0f32; 19];
+            matrix.product(&raw, &mut res).unwrap();
+            assert!(res
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-3f32)));
+        });
+
+        let create = |mean: &[f32], scale: &[f32]| {
+            MatrixF32::create_standardised(
+                Coefficients::Rows(&rows),
+                &intercepts,
+                mean,
+                scale,
+            )
+            .err()
+        };
+        assert_eq!(
+            create(&mean[..5], &scale),
+            Some(EstimatorError::StandardisationLength {
+                expected: 6,
+                actual: 5
+            })
+        );
+        assert_eq!(
+            create(&mean, &[1., 1., 0., 1., 1., 1.]),
+            Some(EstimatorError::InvalidScale { input: 2 })
+        );
+        assert_eq!(
+            create(&mean, &[1., 1., 1., 1., f32::NAN, 1.]),
+            Some(EstimatorError::InvalidScale { input: 4 })
+        );
+        assert_eq!(
+            create(&[0., 0., f32::INFINITY, 0., 0., 0.], &scale),
+            Some(EstimatorError::InvalidMean { input: 2 })
+        );
+        assert_eq!(
+            create(&[0., 0., 0., 0., 0., f32::NAN], &scale),
+            Some(EstimatorError::InvalidMean { input: 5 })
+        );
+    }
+
+    #[test]
+    fn mlp() {
+        use crate::{Activation, MatrixF32, Mlp};
+
+        let layer = |num_inputs: usize, num_outputs: usize, seed: usize| {
+            let intercepts: Vec<f32> = (0..num_outputs)
+                .map(|c| ((c + seed) % 5) as f32 * 0.1 - 0.2)
+                .collect();
+            (fixture_rows(num_inputs, num_outputs), intercepts)
+        };
+        // the same layer as plain arithmetic, with exact activations
+        let reference = |(rows, intercepts): &(Vec<Vec<f32>>, Vec<f32>),
+                         x: &[f32],
+                         f: fn(f32) -> f32| {
+            (0..intercepts.len())
+                .map(|c| f(intercepts[c] + x.iter().zip(rows).map(|(v, r)| v * r[c]).sum::<f32>()))
+                .collect::<Vec<f32>>()
+        };
+        let matrix = |backend: Backend, (rows, intercepts): &(Vec<Vec<f32>>, Vec<f32>)| {
+            MatrixF32::create_with_backend(backend, rows, intercepts).unwrap()
+        };
+
+        // 6 inputs -> 20 (relu) -> 9 (sigmoid) -> 11 (tanh) -> 4
+        let layers = [
+            layer(6, 20, 0),
+            layer(20, 9, 1),
+            layer(9, 11, 2),
+            layer(11, 4, 3),
+        ];
+        let v = [1f32, -2., 0.5, 3., -0.25, 2.];
+        let h = reference(&layers[0], &v, |x| x.max(0.));
+        let h = reference(&layers[1], &h, |x| 1. / (1. + (-x).exp()));
+        let h = reference(&layers[2], &h, f32::tanh);
+        let expected = reference(&layers[3], &h, |x| x);
+
+        for_each_supported_backend(|backend| {
+            let mut net = Mlp::create(
+                vec![
+                    (matrix(backend, &layers[0]), Activation::Relu),
+                    (matrix(backend, &layers[1]), Activation::Sigmoid),
+                    (matrix(backend, &layers[2]), Activation::Tanh),
+                ],
+                matrix(backend, &layers[3]),
+            )
+            .unwrap();
+            assert_eq!((net.num_inputs(), net.num_outputs()), (6, 4));
+
+            let mut res = [0f32; 4];
+            net.product(&v, &mut res).unwrap();
+            assert!(res
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-3f32)));
+
+            // the softmax is the output layer's, on the same hidden values
+            let mut expected_softmax = [0f32; 4];
+            matrix(backend, &layers[3])
+                .product_softmax_cumulative_approx(&h, &mut expected_softmax)
+                .unwrap();
+            net.product_softmax_cumulative_approx(&v, &mut res).unwrap();
+            assert!(res.iter().zip(&expected_softmax).all(|(a, b)| abs_diff_eq!(
+                a,
+                b,
+                epsilon = 1e-3f32 * b.abs().max(1.0)
+            )));
+
+            // no hidden layers is just the output layer
+            let mut single = Mlp::create(vec![], matrix(backend, &layers[0])).unwrap();
+            let mut res = [0f32; 20];
+            single.product(&v, &mut res).unwrap();
+            assert!(res
+                .iter()
+                .zip(&reference(&layers[0], &v, |x| x))
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32)));
+
+            assert!(net.product(&v[..5], &mut [0f32; 4]).is_err());
+            assert!(net.product(&v, &mut [0f32; 5]).is_err());
+        });
+
+        // the second layer doesn't take the first's outputs
+        assert_eq!(
+            Mlp::create(
+                vec![(matrix(Backend::Scalar, &layers[0]), Activation::Relu)],
+                matrix(Backend::Scalar, &layers[2])
+            )
+            .err(),
+            Some(EstimatorError::LayerShape {
+                layer: 1,
+                expected: 20,
+                actual: 9
+            })
+        );
+    }
+
+    #[test]
+    fn activations() {
+        use crate::Activation;
+
+        let apply = |activation: Activation, x: f32| {
+            let mut v = [x];
+            activation.apply(&mut v);
+            v[0]
+        };
+
+        // tanh is exactly odd, and keeps its precision near zero
+        assert_eq!(apply(Activation::Tanh, 0.), 0.);
+        for &x in &[1e-30f32, 1e-6, 1e-4, 0.01, 0.0999, 0.1, 0.5, 1., 3., 10., 100.] {
+            let t = apply(Activation::Tanh, x);
+            assert_eq!(apply(Activation::Tanh, -x), -t);
+            assert!(abs_diff_eq!(t, x.tanh(), epsilon = 1e-3f32 * x.tanh()));
+        }
+        assert_eq!(apply(Activation::Tanh, f32::INFINITY), 1.);
+        assert!(abs_diff_eq!(
+            apply(Activation::Sigmoid, 0.),
+            0.5,
+            epsilon = 1e-4f32
+        ));
+        assert!(apply(Activation::Sigmoid, f32::NEG_INFINITY) < 1e-30);
+
+        // NaN goes through to the next layer, as it would for the product
+        for &activation in &[
+            Activation::Identity,
+            Activation::Relu,
+            Activation::Sigmoid,
+            Activation::Tanh,
+        ] {
+            assert!(apply(activation, f32::NAN).is_nan());
+        }
+    }
+
+    #[test]
+    fn ensemble() {
+        use crate::{Averaging, Ensemble, MatrixF32};
+
+        // 3 members, 5 inputs, 7 outputs, each member's rows further down the fixture
+        let member = |seed: usize| {
+            let rows = fixture_rows(5 + seed, 7).split_off(seed);
+            let intercepts: Vec<f32> = (0..7).map(|c| ((c + seed) % 4) as f32 * 0.2).collect();
+            (rows, intercepts)
+        };
+        let members: Vec<_> = (0..3).map(member).collect();
+        let matrices = |backend: Backend| -> Vec<MatrixF32> {
+            members
+                .iter()
+                .map(|(rows, intercepts)| {
+                    MatrixF32::create_with_backend(backend, rows, intercepts).unwrap()
+                })
+                .collect()
+        };
+        let weights = [1f32, 2., 1.];
+        let v = [0.5f32, -1., 2., 0.25, 1.5];
+
+        // each member's logits as plain arithmetic, and an exact softmax
+        let logits: Vec<Vec<f32>> = members
+            .iter()
+            .map(|(rows, intercepts)| {
+                (0..7)
+                    .map(|c| intercepts[c] + v.iter().zip(rows).map(|(x, r)| x * r[c]).sum::<f32>())
+                    .collect()
+            })
+            .collect();
+        let softmax = |x: &[f32]| -> Vec<f32> {
+            let sum: f32 = x.iter().map(|x| x.exp()).sum();
+            x.iter().map(|x| x.exp() / sum).collect()
+        };
+        let average = |outputs: &[Vec<f32>]| -> Vec<f32> {
+            (0..7)
+                .map(|c| {
+                    outputs
+                        .iter()
+                        .zip(&weights)
+                        .map(|(o, w)| o[c] * w / 4.)
+                        .sum()
+                })
+                .collect()
+        };
+        let average_logits = average(&logits);
+        let probabilities: Vec<Vec<f32>> = logits.iter().map(|l| softmax(l)).collect();
+        let expected = [
+            (Averaging::Probabilities, average(&probabilities)),
+            (Averaging::Logits, softmax(&average_logits)),
+        ];
+
+        for_each_supported_backend(|backend| {
+            let mut ensemble = Ensemble::create_weighted(matrices(backend), &weights).unwrap();
+            assert_eq!(ensemble.weights(), &[0.25, 0.5, 0.25]);
+            assert_eq!((ensemble.num_inputs(), ensemble.num_outputs()), (5, 7));
+
+            let mut res = [0f32; 7];
+            ensemble.product(&v, &mut res).unwrap();
+            assert!(res.iter().zip(&average_logits).all(|(a, b)| abs_diff_eq!(
+                a,
+                b,
+                epsilon = 1e-4f32
+            )));
+            for (averaging, expected) in &expected {
+                ensemble.probabilities(&v, &mut res, *averaging).unwrap();
+                assert!(abs_diff_eq!(
+                    res.iter().sum::<f32>(),
+                    1f32,
+                    epsilon = 1e-4f32
+                ));
+                assert!(res.iter().zip(expected).all(|(a, b)| abs_diff_eq!(
+                    a,
+                    b,
+                    epsilon = 1e-3f32
+                )));
+            }
+        });
+
+        // logits past where the exponential saturates (about 88): the largest is taken
+        // off first, for both kinds of averaging
+        let zeros = vec![vec![0f32; 2]; 5];
+        let large = vec![
+            MatrixF32::create_from_rows(&zeros, &[100., 95.]).unwrap(),
+            MatrixF32::create_from_rows(&zeros, &[100., 90.]).unwrap(),
+        ];
+        let mut ensemble = Ensemble::create(large).unwrap();
+        let p = |gap: f32| 1. / (1. + (-gap).exp());
+        let expected_large = [
+            (Averaging::Probabilities, (p(5.) + p(10.)) / 2.),
+            (Averaging::Logits, p(7.5)),
+        ];
+        let mut res = [0f32; 2];
+        for (averaging, expected) in &expected_large {
+            ensemble.probabilities(&v, &mut res, *averaging).unwrap();
+            assert!(abs_diff_eq!(res[0], expected, epsilon = 1e-4f32));
+            assert!(abs_diff_eq!(res[1], 1. - expected, epsilon = 1e-4f32));
+        }
+
+        // members with no outputs score to nothing
+        let empty = || MatrixF32::from_row_major(&[], 5, 0, &[]).unwrap();
+        let mut ensemble = Ensemble::create(vec![empty(), empty()]).unwrap();
+        assert_eq!(ensemble.num_outputs(), 0);
+        ensemble.product(&v, &mut []).unwrap();
+        for &averaging in &[Averaging::Probabilities, Averaging::Logits] {
+            ensemble.probabilities(&v, &mut [], averaging).unwrap();
+        }
+
+        // equal weights
+        let ensemble = Ensemble::create(matrices(Backend::Scalar)).unwrap();
+        assert!(ensemble
+            .weights()
+            .iter()
+            .all(|w| abs_diff_eq!(*w, 1f32 / 3., epsilon = 1e-6f32)));
+
+        // no members, a member of a different shape, or bad weights
+        assert_eq!(
+            Ensemble::create(vec![]).err(),
+            Some(EstimatorError::NoMembers)
+        );
+        let (rows, intercepts) = &members[0];
+        let mut mismatched = matrices(Backend::Scalar);
+        mismatched.push(MatrixF32::create_from_rows(&rows[..4], intercepts).unwrap());
+        assert_eq!(
+            Ensemble::create(mismatched).err(),
+            Some(EstimatorError::MemberShape {
+                member: 3,
+                expected: (5, 7),
+                actual: (4, 7)
+            })
+        );
+        assert_eq!(
+            Ensemble::create_weighted(matrices(Backend::Scalar), &weights[..2]).err(),
+            Some(EstimatorError::WeightLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        for bad in &[[1f32, -1., 1.], [0f32, 0., 0.], [1f32, f32::NAN, 1.]] {
+            assert_eq!(
+                Ensemble::create_weighted(matrices(Backend::Scalar), bad).err(),
+                Some(EstimatorError::InvalidWeights)
+            );
+        }
+    }
+
+    #[test]
+    fn product_batch() {
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+
+        for matrix in available_estimators(&rows, &intercepts) {
+            // cover whole blocks, partial blocks, and nothing at all
+            for &n_rows in &[0usize, 1, 3, 4, 5, 9] {
+                let inputs: Vec<f32> = (0..n_rows * 5).map(|x| (x % 7) as f32 - 3.0).collect();
+                let mut batch = vec![0f32; n_rows * 35];
+                let mut single = vec![0f32; n_rows * 35];
+
+                // the blocked kernels do the same operations in the same order
+                matrix.product_batch(&inputs, n_rows, &mut batch).unwrap();
+                for (inp, out) in inputs.chunks(5).zip(single.chunks_mut(35)) {
+                    matrix.product(inp, out).unwrap();
+                }
+                assert_eq!(batch, single);
+
+                matrix
+                    .softmax_cumulative_batch(&inputs, n_rows, &mut batch)
+                    .unwrap();
+                for (inp, out) in inputs.chunks(5).zip(single.chunks_mut(35)) {
+                    matrix.product_softmax_cumulative_approx(inp, out).unwrap();
+                }
+                assert_eq!(batch, single);
+            }
+
+            // wrong dimensions
+            let inputs = vec![0f32; 2 * 5];
+            let mut outputs = vec![0f32; 2 * 35];
+            assert!(matrix.product_batch(&inputs, 3, &mut outputs).is_err());
+            assert!(matrix.product_batch(&inputs[..9], 2, &mut outputs).is_err());
+            assert!(matrix
+                .softmax_cumulative_batch(&inputs, 2, &mut outputs[..69])
+                .is_err());
+
+            // a number of rows whose lengths overflow
+            let huge = usize::MAX / 2 + 1;
+            assert_eq!(
+                matrix.product_batch(&inputs, huge, &mut outputs),
+                Err(EstimatorError::InputLength {
+                    expected: usize::MAX,
+                    actual: 10
+                })
+            );
+            assert!(matrix
+                .softmax_cumulative_batch(&inputs, huge, &mut outputs)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn product_sparse() {
+        let coeffs: Vec<f32> = (1..=(35 * 12)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+
+        // rows 1, 4 and 10 set, in no particular order; row 4 listed twice
+        let indices = [10u32, 4, 1, 4];
+        let values = [0.5f32, 2.0, -1.5, 1.0];
+        let mut dense = vec![0f32; 12];
+        dense[1] = -1.5;
+        dense[4] = 3.0;
+        dense[10] = 0.5;
+
+        for matrix in available_estimators(&rows, &intercepts) {
+            let mut expected = vec![0f32; 35];
+            let mut res = vec![0f32; 35];
+
+            matrix.product(&dense, &mut expected).unwrap();
+            matrix.product_sparse(&indices, &values, &mut res).unwrap();
+            assert!(res
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-5f32)));
+
+            matrix
+                .product_softmax_cumulative_approx(&dense, &mut expected)
+                .unwrap();
+            matrix
+                .softmax_cumulative_sparse(&indices, &values, &mut res)
+                .unwrap();
+            assert!(res
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-4f32)));
+
+            // nothing set gives just the intercepts
+            matrix.product_sparse(&[], &[], &mut res).unwrap();
+            assert_eq!(res, intercepts);
+
+            // out of range index, mismatched lengths, wrong output size
+            assert!(matrix.product_sparse(&[12], &[1.0], &mut res).is_err());
+            assert!(matrix.product_sparse(&[1, 2], &[1.0], &mut res).is_err());
+            assert!(matrix
+                .softmax_cumulative_sparse(&[1], &[1.0], &mut res[..34])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn sparse_coefficients() {
+        use crate::SparseMatrixF32;
+
+        // 30 inputs, 20 outputs, with a handful of non-zero coefficients
+        let triplets = [
+            (0u32, 0u32, 1.0f32),
+            (3, 19, -2.0),
+            (3, 7, 0.5),
+            (29, 8, 0.25),
+            (12, 0, 3.0),
+            (12, 0, 1.0), // repeated; adds up to 4
+            (5, 5, 1.5),
+            (5, 5, -1.5), // cancels out
+        ];
+        let intercepts: Vec<f32> = (0..20).map(|x| (x as f32) * 0.01).collect();
+
+        let mut rows = vec![vec![0f32; 20]; 30];
+        for &(i, o, v) in &triplets {
+            rows[i as usize][o as usize] += v;
+        }
+
+        // the same matrix in CSR form: (output, value) for each input in turn
+        let mut row_offsets = vec![0usize];
+        let mut output_indices = Vec::new();
+        let mut values = Vec::new();
+        for r in &rows {
+            for (o, v) in r.iter().enumerate().filter(|(_, v)| **v != 0.0) {
+                output_indices.push(o as u32);
+                values.push(*v);
+            }
+            row_offsets.push(values.len());
+        }
+
+        let dense = crate::matrix_scalar::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        let from_triplets = SparseMatrixF32::from_triplets(30, 20, &triplets, &intercepts).unwrap();
+        let from_csr =
+            SparseMatrixF32::from_csr(30, 20, &row_offsets, &output_indices, &values, &intercepts)
+                .unwrap();
+
+        let v: Vec<f32> = (0..30).map(|x| (x % 5) as f32 - 2.0).collect();
+        let mut expected = vec![0f32; 20];
+        let mut expected_softmax = vec![0f32; 20];
+        dense.product(&v, &mut expected).unwrap();
+        dense
+            .product_softmax_cumulative_approx(&v, &mut expected_softmax)
+            .unwrap();
+
+        for sparse in &[from_triplets, from_csr] {
+            assert_eq!(sparse.num_nonzero(), 5);
+            assert!((sparse.density() - 5.0 / 600.0).abs() < 1e-12);
+
+            let mut res = vec![0f32; 20];
+            sparse.product(&v, &mut res).unwrap();
+            assert_eq!(res, expected);
+            sparse.product_softmax_cumulative_approx(&v, &mut res).unwrap();
+            assert_eq!(res, expected_softmax);
+        }
+
+        // out of range, or inconsistent
+        assert!(SparseMatrixF32::from_triplets(30, 20, &[(30, 0, 1.0)], &intercepts).is_err());
+        assert!(SparseMatrixF32::from_triplets(30, 20, &[(0, 20, 1.0)], &intercepts).is_err());
+        assert!(SparseMatrixF32::from_triplets(30, 20, &[], &intercepts[..19]).is_err());
+        assert!(SparseMatrixF32::from_csr(
+            30,
+            20,
+            &row_offsets[..30],
+            &output_indices,
+            &values,
+            &intercepts
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn quantized_i8() {
+        use crate::matrix_dispatch::Backend;
+        use crate::MatrixI8;
+
+        // 40 inputs, 21 outputs; output 0 spans exactly -128..=127 so it quantizes
+        // without loss, and output 1 is all zero
+        let rows: Vec<Vec<f32>> = (0..40)
+            .map(|i| {
+                (0..21)
+                    .map(|o| match o {
+                        0 if i == 0 => -128.0,
+                        0 if i == 1 => 127.0,
+                        0 => (i as f32) - 20.0,
+                        1 => 0.0,
+                        _ => ((i * 7 + o * 13) % 17) as f32 * 0.05 - 0.4,
+                    })
+                    .collect()
+            })
+            .collect();
+        let intercepts: Vec<f32> = (0..21).map(|x| (x as f32) * 0.1 - 1.0).collect();
+        let samples: Vec<Vec<f32>> = (0..10)
+            .map(|s| (0..40).map(|i| ((i + s) % 7) as f32 * 0.1 - 0.3).collect())
+            .collect();
+
+        let (default, report) = MatrixI8::quantize(&rows, &intercepts, &samples).unwrap();
+        // half a step of the widest column
+        assert!(report.max_coefficient_error <= 0.8 / 255.0 / 2.0 + 1e-6);
+        assert!(report.max_abs_error > 0.0 && report.max_abs_error < 0.02);
+
+        let dense = crate::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        let mut expected = vec![0f32; 21];
+        let mut expected_softmax = vec![0f32; 21];
+        let mut res = vec![0f32; 21];
+
+        let matrices: Vec<MatrixI8> = [Backend::Avx, Backend::Neon, Backend::Scalar]
+            .iter()
+            .filter_map(|&b| MatrixI8::create_with_backend(b, &rows, &intercepts).ok())
+            .chain(std::iter::once(default))
+            .collect();
+        for matrix in &matrices {
+            for v in &samples {
+                dense.product(v, &mut expected).unwrap();
+                matrix.product(v, &mut res).unwrap();
+                assert!(abs_diff_eq!(res[0], expected[0], epsilon = 1e-4));
+                assert_eq!(res[1], intercepts[1]);
+                assert!(res
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| (a - b).abs() <= report.max_abs_error + 1e-5));
+
+                dense
+                    .product_softmax_cumulative_approx(v, &mut expected_softmax)
+                    .unwrap();
+                matrix
+                    .product_softmax_cumulative_approx(v, &mut res)
+                    .unwrap();
+                assert!(res
+                    .iter()
+                    .zip(&expected_softmax)
+                    .all(|(a, b)| ((a - b) / b).abs() < 0.02));
+            }
+
+            // wrong dimensions
+            assert!(matrix.product(&samples[0][..39], &mut res).is_err());
+            assert!(matrix
+                .product_softmax_cumulative_approx(&samples[0], &mut res[..20])
+                .is_err());
+        }
+
+        assert!(MatrixI8::create_from_rows(&rows, &intercepts[..20]).is_err());
+    }
+
+    #[test]
+    fn half_precision() {
+        use crate::estimator::LinearEstimator;
+        use crate::matrix_dispatch::Backend;
+        use crate::{MatrixBF16, MatrixF16};
+
+        // 30 inputs, 19 outputs
+        let rows: Vec<Vec<f32>> = (0..30)
+            .map(|i| {
+                (0..19)
+                    .map(|o| ((i * 7 + o * 13) % 23) as f32 * 0.0137 - 0.15)
+                    .collect()
+            })
+            .collect();
+        let intercepts: Vec<f32> = (0..19).map(|x| (x as f32) * 0.1 - 1.0).collect();
+        let dense = crate::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+
+        let backends = [Backend::Avx, Backend::Neon, Backend::Scalar];
+        let f16 = backends
+            .iter()
+            .filter_map(|&b| MatrixF16::create_with_backend(b, &rows, &intercepts).ok())
+            .chain(MatrixF16::create_from_rows(&rows, &intercepts))
+            .map(|m| Box::new(m) as Box<dyn LinearEstimator>);
+        let bf16 = backends
+            .iter()
+            .filter_map(|&b| MatrixBF16::create_with_backend(b, &rows, &intercepts).ok())
+            .chain(MatrixBF16::create_from_rows(&rows, &intercepts))
+            .map(|m| Box::new(m) as Box<dyn LinearEstimator>);
+
+        // relative error per coefficient is 2^-11 for f16, 2^-8 for bf16; the
+        // product sums 30 of them, each at most 0.3 * 2.0
+        let matrices: Vec<(f32, Box<dyn LinearEstimator>)> = f16
+            .map(|m| (30.0 * 0.6 * 2f32.powi(-11), m))
+            .chain(bf16.map(|m| (30.0 * 0.6 * 2f32.powi(-8), m)))
+            .collect();
+        // at least the scalar and default one of each
+        assert!(matrices.len() >= 4);
+
+        let mut expected = vec![0f32; 19];
+        let mut res = vec![0f32; 19];
+        for (tolerance, matrix) in &matrices {
+            for s in 0..5 {
+                let v: Vec<f32> = (0..30).map(|i| ((i + s) % 9) as f32 * 0.25 - 1.0).collect();
+
+                dense.product(&v, &mut expected).unwrap();
+                matrix.product(&v, &mut res).unwrap();
+                assert!(res
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| (a - b).abs() <= *tolerance));
+
+                dense
+                    .product_softmax_cumulative_approx(&v, &mut expected)
+                    .unwrap();
+                matrix
+                    .product_softmax_cumulative_approx(&v, &mut res)
+                    .unwrap();
+                assert!(res
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| ((a - b) / b).abs() < 2.0 * tolerance));
+            }
+
+            // wrong dimensions
+            assert!(matrix.product(&[0f32; 29], &mut res).is_err());
+            assert!(matrix
+                .product_softmax_cumulative_approx(&[0f32; 30], &mut res[..18])
+                .is_err());
+        }
+
+        // coefficients that fit exactly give exactly the f32 result
+        let exact = vec![vec![0.5f32, -2.0, 0.375], vec![1.0f32, 0.25, -8.0]];
+        let exact_intercepts = [0.1f32, 0.2, 0.3];
+        let dense = crate::MatrixF32::create_from_rows(&exact, &exact_intercepts).unwrap();
+        let f16 = MatrixF16::create_from_rows(&exact, &exact_intercepts).unwrap();
+        let bf16 = MatrixBF16::create_from_rows(&exact, &exact_intercepts).unwrap();
+        let mut expected = vec![0f32; 3];
+        dense.product(&[1.5, -0.5], &mut expected).unwrap();
+        for matrix in &[&f16 as &dyn LinearEstimator, &bf16] {
+            let mut res = vec![0f32; 3];
+            matrix.product(&[1.5, -0.5], &mut res).unwrap();
+            assert_eq!(res, expected);
+        }
+
+        assert!(MatrixF16::create_from_rows(&rows, &intercepts[..18]).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_product_batch() {
+        let coeffs: Vec<f32> = (1..=(35 * 5)).map(|x| (x as f32) * 0.001).collect();
+        let rows: Vec<Vec<f32>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts: Vec<f32> = (0..35).map(|x| (x as f32) * -0.01).collect();
+
+        let n_rows = 1001;
+        let inputs: Vec<f32> = (0..n_rows * 5).map(|x| (x % 11) as f32 - 5.0).collect();
+        let mut sequential = vec![0f32; n_rows * 35];
+        let mut parallel = vec![0f32; n_rows * 35];
+
+        for matrix in available_estimators(&rows, &intercepts) {
+            matrix
+                .product_batch(&inputs, n_rows, &mut sequential)
+                .unwrap();
+            // including chunk sizes that aren't a whole number of blocks
+            for &min_chunk_rows in &[0usize, 1, 7, 64, 5000] {
+                matrix
+                    .par_product_batch(&inputs, n_rows, &mut parallel, min_chunk_rows)
+                    .unwrap();
+                assert_eq!(parallel, sequential);
+            }
+
+            matrix
+                .softmax_cumulative_batch(&inputs, n_rows, &mut sequential)
+                .unwrap();
+            matrix
+                .par_softmax_cumulative_batch(&inputs, n_rows, &mut parallel, 64)
+                .unwrap();
+            assert_eq!(parallel, sequential);
+
+            assert!(matrix
+                .par_product_batch(&inputs, n_rows + 1, &mut parallel, 64)
+                .is_err());
+            assert!(matrix
+                .par_softmax_cumulative_batch(&inputs, usize::MAX / 2 + 1, &mut parallel, 64)
+                .is_err());
+        }
+
+        // and the inherent methods on the default matrix
+        let matrix = crate::MatrixF32::create_from_rows(&rows, &intercepts).unwrap();
+        matrix
+            .product_batch(&inputs, n_rows, &mut sequential)
+            .unwrap();
+        matrix
+            .par_product_batch(&inputs, n_rows, &mut parallel, 100)
+            .unwrap();
+        assert_eq!(parallel, sequential);
+    }
+
+    // every f64 implementation supported on this machine
+    fn available_f64(rows: &[Vec<f64>], intercepts: &[f64]) -> Vec<crate::MatrixF64> {
+        use crate::matrix_dispatch::Backend;
+
+        [Backend::Avx, Backend::Neon, Backend::Scalar]
+            .iter()
+            .filter_map(|&b| crate::MatrixF64::create_with_backend(b, rows, intercepts).ok())
+            .collect()
+    }
+
+    #[test]
+    fn product_f64() {
+        // large intercepts with small effects: in f32 these would be lost entirely
+        let rows = vec![vec![1e-5f64, 2e-5, 3e-5], vec![4e-5f64, 5e-5, 6e-5]];
+        let intercepts = [1e4f64, 2e4, 3e4];
+        let matrices = available_f64(&rows, &intercepts);
+        assert!(!matrices.is_empty());
+        assert!(crate::MatrixF64::create_from_rows(&rows, &intercepts).is_ok());
+
+        for matrix in matrices {
+            let mut res = vec![0f64; 3];
+            matrix.product(&[1.0, 2.0], &mut res).unwrap();
+            let effects: Vec<f64> = res.iter().zip(&intercepts).map(|(r, i)| r - i).collect();
+            assert!(effects
+                .iter()
+                .zip(&[9e-5f64, 12e-5, 15e-5])
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-10f64)));
+
+            // wrong dimensions
+            assert!(matrix.product(&[1.0], &mut res).is_err());
+            assert!(matrix
+                .product_softmax_cumulative_approx(&[1.0, 2.0], &mut res[..2])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn product_large_f64() {
+        let coeffs: Vec<f64> = (1..=(35 * 5)).map(|x| x as f64).collect();
+        let rows: Vec<Vec<f64>> = coeffs[..].chunks(35).map(|c| c.to_vec()).collect();
+        let intercepts = [0f64; 35];
+
+        for matrix in available_f64(&rows, &intercepts) {
+            let v: Vec<f64> = (1..=5).map(|x| x as f64).collect();
+            let mut res = vec![0f64; 35];
+            matrix.product(&v, &mut res).unwrap();
+            assert_eq!(res[0], 1415_f64);
+            assert_eq!(res[18], 1685_f64);
+            assert_eq!(res[34], 1925_f64);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)] // values as printed by R
+    fn product_softmax_f64() {
+        // same as `product_softmax`, but much tighter
+        let rows = vec![vec![1.0f64, 2.0, 3.0], vec![4.0f64, 5.0, 6.0]];
+        let intercepts = [0.1f64, 0.2f64, 0.3f64];
+        for matrix in available_f64(&rows, &intercepts) {
+            let mut res = vec![0f64; 3];
+            matrix
+                .product_softmax_cumulative_approx(&[0.1, 0.5], &mut res)
+                .unwrap();
+            assert!(res
+                .iter()
+                .zip(&[9.025013_f64, 27.199159_f64, 63.797393_f64])
+                .all(|(a, b)| abs_diff_eq!(a, b, epsilon = 1e-5f64)));
+        }
     }
 }
@@ -0,0 +1,179 @@
+use crate::error::{check_lengths, EstimatorError};
+use crate::exp_approx::exp_approx_f32;
+use crate::matrix_dispatch::MatrixF32;
+
+// applied to a layer's outputs before they're passed on to the next layer. Each is
+// a plain loop over the values with no branches, only selects, so the compiler
+// vectorises it; sigmoid and tanh use the same approximate exponential as the
+// softmax, and pass NaN through rather than giving a finite value for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activation {
+    Identity,
+    Relu,
+    Sigmoid,
+    Tanh,
+}
+
+// below this, tanh is taken from its series: the form with the exponential is the
+// difference of two nearly equal numbers there, which loses most of the precision
+const TANH_SERIES_LIMIT: f32 = 0.1;
+
+impl Activation {
+    pub fn apply(self, values: &mut [f32]) {
+        match self {
+            Activation::Identity => {}
+            // not `max`, which would give 0 for NaN
+            Activation::Relu => values
+                .iter_mut()
+                .for_each(|v| *v = if *v < 0f32 { 0f32 } else { *v }),
+            Activation::Sigmoid => values.iter_mut().for_each(|v| *v = sigmoid(*v)),
+            Activation::Tanh => values.iter_mut().for_each(|v| *v = tanh(*v)),
+        }
+    }
+}
+
+#[inline(always)]
+fn sigmoid(x: f32) -> f32 {
+    let s = 1f32 / (1f32 + exp_approx_f32(-x));
+    if x.is_nan() {
+        x
+    } else {
+        s
+    }
+}
+
+// 1 - 2 / (e^2|x| + 1), which saturates to 1 without overflow, with the sign put
+// back after so the result is exactly odd
+#[inline(always)]
+fn tanh(x: f32) -> f32 {
+    let a = x.abs();
+    let t = if a < TANH_SERIES_LIMIT {
+        let a2 = a * a;
+        a * (1f32 - a2 * (1f32 / 3f32 - a2 * (2f32 / 15f32)))
+    } else {
+        1f32 - 2f32 / (exp_approx_f32(2f32 * a) + 1f32)
+    };
+    if x.is_nan() {
+        x
+    } else {
+        t.copysign(x)
+    }
+}
+
+// a small multi-layer perceptron: hidden layers, each a `MatrixF32` followed by an
+// activation, then a linear output layer, which can also give the cumulative
+// softmax. The outputs of the hidden layers go to two scratch buffers, alternately,
+// sized for the widest layer, so scoring doesn't allocate; that does mean scoring
+// takes `&mut self`, so this isn't a `LinearEstimator`. Use one per thread.
+pub struct Mlp {
+    hidden: Vec<(MatrixF32, Activation)>,
+    output: MatrixF32,
+    scratch: [Vec<f32>; 2],
+}
+
+impl Mlp {
+    // each layer's inputs must match the outputs of the one before
+    pub fn create(
+        hidden: Vec<(MatrixF32, Activation)>,
+        output: MatrixF32,
+    ) -> Result<Self, EstimatorError> {
+        let widths = hidden.iter().map(|(m, _)| m).chain(Some(&output));
+        for (layer, (previous, next)) in widths.clone().zip(widths.skip(1)).enumerate() {
+            if next.num_rows != previous.num_columns {
+                return Err(EstimatorError::LayerShape {
+                    layer: layer + 1,
+                    expected: previous.num_columns,
+                    actual: next.num_rows,
+                });
+            }
+        }
+
+        let widest = hidden.iter().map(|(m, _)| m.num_columns).max().unwrap_or(0);
+        Ok(Self {
+            hidden,
+            output,
+            scratch: [vec![0f32; widest], vec![0f32; widest]],
+        })
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        match self.hidden.first() {
+            Some((first, _)) => first.num_rows,
+            None => self.output.num_rows,
+        }
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.output.num_columns
+    }
+
+    pub fn hidden_layers(&self) -> &[(MatrixF32, Activation)] {
+        &self.hidden
+    }
+
+    pub fn output_layer(&self) -> &MatrixF32 {
+        &self.output
+    }
+
+    // the final layer is linear: y = h * [coeff] + [intercepts]
+    pub fn product(
+        &mut self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_inputs(),
+            self.num_outputs(),
+        )?;
+        let inputs = run_hidden(&self.hidden, &mut self.scratch, values)?;
+        self.output.product(inputs, destination)
+    }
+
+    // cumulative sum of the approximate exponential of the above (not normalised)
+    pub fn product_softmax_cumulative_approx(
+        &mut self,
+        values: &[f32],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        check_lengths(
+            values.len(),
+            destination.len(),
+            self.num_inputs(),
+            self.num_outputs(),
+        )?;
+        let inputs = run_hidden(&self.hidden, &mut self.scratch, values)?;
+        self.output
+            .product_softmax_cumulative_approx(inputs, destination)
+    }
+}
+
+// run the hidden layers, layer `i` writing to `scratch[i % 2]` and reading from the
+// other; returns the last layer's outputs, which are the inputs to the output layer
+fn run_hidden<'a>(
+    hidden: &[(MatrixF32, Activation)],
+    scratch: &'a mut [Vec<f32>; 2],
+    values: &'a [f32],
+) -> Result<&'a [f32], EstimatorError> {
+    for (i, (layer, activation)) in hidden.iter().enumerate() {
+        let [even, odd] = &mut *scratch;
+        let (previous, dst) = if i % 2 == 0 {
+            (&*odd, even)
+        } else {
+            (&*even, odd)
+        };
+        let src = if i == 0 {
+            values
+        } else {
+            &previous[..layer.num_rows]
+        };
+        let dst = &mut dst[..layer.num_columns];
+        layer.product(src, dst)?;
+        activation.apply(dst);
+    }
+    Ok(match hidden.last() {
+        Some((last, _)) => &scratch[(hidden.len() - 1) % 2][..last.num_columns],
+        None => values,
+    })
+}
@@ -0,0 +1,838 @@
+// coefficient storage shared by the matrix implementations
+//
+// the coefficients and intercepts are packed into one buffer of intrinsics, column
+// chunk major. Each chunk covers `LANES` outputs, and holds the intercepts followed
+// by one intrinsic per input row:
+//
+//      chunk 0:  [intercepts 0..L] [row 0, outputs 0..L] ... [row n-1, outputs 0..L]
+//      chunk 1:  [intercepts L..2L] [row 0, outputs L..2L] ... [row n-1, outputs L..2L]
+//      ...
+//
+// so each chunk is `num_rows + 1` intrinsics, and the product walks straight through
+// memory with no pointer chasing between chunks. The final chunk is padded with zeros
+// where the number of outputs isn't a multiple of `LANES`. The buffer is a `Vec` of
+// the intrinsic type, so it has that type's alignment: 32 bytes for AVX, 64 bytes for
+// AVX-512, 16 bytes for SSE and NEON.
+
+use std::ops::AddAssign;
+
+use crate::batch::BATCH_BLOCK;
+use crate::error::{check_intercepts, row_dimensions, EstimatorError};
+
+/// An intrinsic (or plain array) that holds exactly `LANES` values of `Elem`.
+///
+/// # Safety
+/// The type must have the same size as `[Elem; LANES]`, and an alignment that's a
+/// multiple of `Elem`'s, so it can be viewed as a slice of them.
+pub unsafe trait Lanes: Copy {
+    type Elem: Copy + Default;
+    const LANES: usize;
+}
+
+unsafe impl<T: Copy + Default, const N: usize> Lanes for [T; N] {
+    type Elem = T;
+    const LANES: usize = N;
+}
+
+pub fn as_slice<V: Lanes>(v: &V) -> &[V::Elem] {
+    // safe: guaranteed by the `Lanes` implementation
+    unsafe { std::slice::from_raw_parts(v as *const V as *const V::Elem, V::LANES) }
+}
+
+pub fn as_mut_slice<V: Lanes>(v: &mut V) -> &mut [V::Elem] {
+    // safe: guaranteed by the `Lanes` implementation
+    unsafe { std::slice::from_raw_parts_mut(v as *mut V as *mut V::Elem, V::LANES) }
+}
+
+// a zeroed intrinsic; all-zero bits are zero for every float and integer lane type
+pub fn zeros<V: Lanes>() -> V {
+    unsafe { std::mem::zeroed() }
+}
+
+pub struct PackedColumns<V: Lanes> {
+    num_outputs: usize,
+    num_chunks: usize,
+    // intrinsics per chunk: the intercepts, then one per row
+    stride: usize,
+    data: Vec<V>,
+}
+
+// the coefficients a matrix is created from, in one of the layouts callers have
+// them in; inputs are rows, and outputs are columns
+#[derive(Clone, Copy, Debug)]
+pub enum Coefficients<'a, E> {
+    // one `Vec` per input, holding one coefficient per output
+    Rows(&'a [Vec<E>]),
+    // likewise, as borrowed slices
+    Slices(&'a [&'a [E]]),
+    // flat, with the outputs for each input contiguous: `data[input * num_outputs + output]`
+    RowMajor {
+        data: &'a [E],
+        num_inputs: usize,
+        num_outputs: usize,
+    },
+    // flat, with the inputs for each output contiguous: `data[output * num_inputs + input]`
+    ColumnMajor {
+        data: &'a [E],
+        num_inputs: usize,
+        num_outputs: usize,
+    },
+}
+
+impl<'a, E: Copy> Coefficients<'a, E> {
+    // (inputs, outputs), checking there are some inputs, the rows all have the same
+    // length, and a flat buffer is the right length
+    pub fn dimensions(&self) -> Result<(usize, usize), EstimatorError> {
+        match *self {
+            Coefficients::Rows(rows) => row_dimensions(rows),
+            Coefficients::Slices(rows) => row_dimensions(rows),
+            Coefficients::RowMajor {
+                data,
+                num_inputs,
+                num_outputs,
+            }
+            | Coefficients::ColumnMajor {
+                data,
+                num_inputs,
+                num_outputs,
+            } => {
+                if num_inputs == 0 {
+                    return Err(EstimatorError::NoInputs);
+                }
+                match num_inputs.checked_mul(num_outputs) {
+                    Some(expected) if expected == data.len() => Ok((num_inputs, num_outputs)),
+                    _ => Err(EstimatorError::CoefficientLength {
+                        expected: num_inputs.saturating_mul(num_outputs),
+                        actual: data.len(),
+                    }),
+                }
+            }
+        }
+    }
+
+    // within range: only called after `dimensions` has checked them
+    pub(crate) fn get(&self, input: usize, output: usize) -> E {
+        match *self {
+            Coefficients::Rows(rows) => rows[input][output],
+            Coefficients::Slices(rows) => rows[input][output],
+            Coefficients::RowMajor {
+                data, num_outputs, ..
+            } => data[input * num_outputs + output],
+            Coefficients::ColumnMajor {
+                data, num_inputs, ..
+            } => data[output * num_inputs + input],
+        }
+    }
+}
+
+impl<'a, E: Copy + Into<f64>> Coefficients<'a, E> {
+    // check every coefficient and intercept is finite, reporting the first one that
+    // isn't; the dimensions are checked first
+    pub fn check_finite(&self, intercepts: &[E]) -> Result<(), EstimatorError> {
+        let (num_inputs, num_outputs) = self.dimensions()?;
+        for input in 0..num_inputs {
+            for output in 0..num_outputs {
+                if !self.get(input, output).into().is_finite() {
+                    return Err(EstimatorError::NonFiniteCoefficient { input, output });
+                }
+            }
+        }
+        match intercepts.iter().position(|i| !(*i).into().is_finite()) {
+            Some(output) => Err(EstimatorError::NonFiniteIntercept { output }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Coefficients<'a, f32> {
+    // fold a standardisation of the inputs, `(x - mean) / scale`, into the
+    // coefficients and intercepts, so that raw inputs give the same outputs as
+    // standardised ones did: each coefficient is divided by its input's scale, and
+    // the intercepts take off the mean times that. Returns the coefficients (row
+    // major) and intercepts; the intercepts are summed in f64.
+    pub fn fold_standardisation(
+        &self,
+        intercepts: &[f32],
+        mean: &[f32],
+        scale: &[f32],
+    ) -> Result<(Vec<f32>, Vec<f32>), EstimatorError> {
+        let (num_inputs, num_outputs) = self.dimensions()?;
+        check_intercepts(intercepts.len(), num_outputs)?;
+        for len in [mean.len(), scale.len()] {
+            if len != num_inputs {
+                return Err(EstimatorError::StandardisationLength {
+                    expected: num_inputs,
+                    actual: len,
+                });
+            }
+        }
+        if let Some(input) = scale.iter().position(|s| *s == 0f32 || !s.is_finite()) {
+            return Err(EstimatorError::InvalidScale { input });
+        }
+        if let Some(input) = mean.iter().position(|m| !m.is_finite()) {
+            return Err(EstimatorError::InvalidMean { input });
+        }
+
+        let mut data = Vec::with_capacity(num_inputs * num_outputs);
+        let mut folded: Vec<f64> = intercepts.iter().map(|i| *i as f64).collect();
+        for input in 0..num_inputs {
+            for (output, intercept) in folded.iter_mut().enumerate() {
+                let c = self.get(input, output) / scale[input];
+                *intercept -= c as f64 * mean[input] as f64;
+                data.push(c);
+            }
+        }
+        Ok((data, folded.into_iter().map(|i| i as f32).collect()))
+    }
+}
+
+impl<V: Lanes> PackedColumns<V> {
+    // `rows` are the inputs, each holding one coefficient per output
+    pub fn from_rows(
+        rows: &[Vec<V::Elem>],
+        intercepts: &[V::Elem],
+    ) -> Result<Self, EstimatorError> {
+        Self::from_coefficients(Coefficients::Rows(rows), intercepts)
+    }
+
+    pub fn from_coefficients(
+        coefficients: Coefficients<V::Elem>,
+        intercepts: &[V::Elem],
+    ) -> Result<Self, EstimatorError> {
+        let (num_rows, num_outputs) = coefficients.dimensions()?;
+        check_intercepts(intercepts.len(), num_outputs)?;
+
+        let num_chunks = num_outputs.div_ceil(V::LANES);
+        let stride = num_rows + 1;
+        let mut data = vec![zeros::<V>(); num_chunks * stride];
+
+        for (chunk_num, chunk) in data.chunks_exact_mut(stride).enumerate() {
+            let start = chunk_num * V::LANES;
+            let end = (start + V::LANES).min(num_outputs);
+            as_mut_slice(&mut chunk[0])[..end - start].copy_from_slice(&intercepts[start..end]);
+            for (row, dest) in chunk[1..].iter_mut().enumerate() {
+                for (lane, output) in as_mut_slice(dest).iter_mut().zip(start..end) {
+                    *lane = coefficients.get(row, output);
+                }
+            }
+        }
+
+        Ok(Self {
+            num_outputs,
+            num_chunks,
+            stride,
+            data,
+        })
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.num_chunks
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.stride - 1
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.num_outputs
+    }
+
+    // the lanes at `entry` of a chunk: 0 for the intercepts, `row + 1` for a row
+    fn lanes(&self, chunk: usize, entry: usize) -> &[V::Elem] {
+        as_slice(&self.data[chunk * self.stride + entry])
+    }
+
+    fn lanes_mut(&mut self, chunk: usize, entry: usize) -> &mut [V::Elem] {
+        as_mut_slice(&mut self.data[chunk * self.stride + entry])
+    }
+
+    // one value per output from `entry` of every chunk, without the padding
+    fn entry(&self, entry: usize) -> Vec<V::Elem> {
+        (0..self.num_chunks)
+            .flat_map(|chunk| self.lanes(chunk, entry).iter().copied())
+            .take(self.num_outputs)
+            .collect()
+    }
+
+    pub fn intercepts(&self) -> Vec<V::Elem> {
+        self.entry(0)
+    }
+
+    pub fn rows(&self) -> Vec<Vec<V::Elem>> {
+        (1..self.stride).map(|entry| self.entry(entry)).collect()
+    }
+
+    pub fn coefficient(&self, row: usize, output: usize) -> Option<V::Elem> {
+        if row >= self.num_rows() || output >= self.num_outputs {
+            return None;
+        }
+        Some(self.lanes(output / V::LANES, row + 1)[output % V::LANES])
+    }
+
+    // (row, output, coefficient) for every coefficient, a row at a time
+    pub fn coefficients(&self) -> impl Iterator<Item = (usize, usize, V::Elem)> + '_ {
+        (0..self.num_rows()).flat_map(move |row| {
+            (0..self.num_outputs).map(move |output| {
+                let value = self.lanes(output / V::LANES, row + 1)[output % V::LANES];
+                (row, output, value)
+            })
+        })
+    }
+
+    // write one value per output into `entry` of every chunk; the padding lanes of
+    // the final chunk are left alone, so they stay zero. The length is checked by
+    // the caller.
+    fn set_entry(&mut self, entry: usize, values: &[V::Elem]) {
+        for (chunk, v) in values.chunks(V::LANES).enumerate() {
+            self.lanes_mut(chunk, entry)[..v.len()].copy_from_slice(v);
+        }
+    }
+
+    fn check_output(&self, output: usize) -> Result<(), EstimatorError> {
+        if output >= self.num_outputs {
+            return Err(EstimatorError::OutputIndex {
+                index: output,
+                num_outputs: self.num_outputs,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_row(&self, row: usize) -> Result<(), EstimatorError> {
+        if row >= self.num_rows() {
+            return Err(EstimatorError::InputIndex {
+                index: row,
+                num_inputs: self.num_rows(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_intercepts(&mut self, intercepts: &[V::Elem]) -> Result<(), EstimatorError> {
+        check_intercepts(intercepts.len(), self.num_outputs)?;
+        self.set_entry(0, intercepts);
+        Ok(())
+    }
+
+    pub fn add_to_intercepts(&mut self, deltas: &[V::Elem]) -> Result<(), EstimatorError>
+    where
+        V::Elem: AddAssign,
+    {
+        check_intercepts(deltas.len(), self.num_outputs)?;
+        for (chunk, d) in deltas.chunks(V::LANES).enumerate() {
+            self.lanes_mut(chunk, 0)
+                .iter_mut()
+                .zip(d)
+                .for_each(|(i, d)| *i += *d);
+        }
+        Ok(())
+    }
+
+    pub fn set_row(&mut self, row: usize, coefficients: &[V::Elem]) -> Result<(), EstimatorError> {
+        self.check_row(row)?;
+        if coefficients.len() != self.num_outputs {
+            return Err(EstimatorError::CoefficientLength {
+                expected: self.num_outputs,
+                actual: coefficients.len(),
+            });
+        }
+        self.set_entry(row + 1, coefficients);
+        Ok(())
+    }
+
+    pub fn set_coefficient(
+        &mut self,
+        row: usize,
+        output: usize,
+        value: V::Elem,
+    ) -> Result<(), EstimatorError> {
+        self.check_row(row)?;
+        self.check_output(output)?;
+        self.lanes_mut(output / V::LANES, row + 1)[output % V::LANES] = value;
+        Ok(())
+    }
+
+    // (intercepts, one intrinsic per row) for each column chunk in turn
+    #[inline(always)]
+    pub fn chunks(&self) -> impl Iterator<Item = (&V, &[V])> {
+        self.data.chunks_exact(self.stride).map(|c| (&c[0], &c[1..]))
+    }
+
+    // whether the product kernels should work on the column chunks in tiles: there
+    // has to be at least a tile's worth of chunks, and enough inputs (see
+    // `TILE_MIN_ROWS`)
+    pub fn tiled(&self) -> bool {
+        self.num_chunks >= TILE && self.num_rows() >= TILE_MIN_ROWS
+    }
+
+    // the intercepts and rows of one column chunk
+    #[inline(always)]
+    pub fn chunk(&self, chunk: usize) -> (&V, &[V]) {
+        let c = &self.data[chunk * self.stride..(chunk + 1) * self.stride];
+        (&c[0], &c[1..])
+    }
+
+    // the chunks `TILE` at a time, as (intercepts, rows) for each chunk of the tile;
+    // chunks left over at the end that don't fill a tile are skipped
+    #[inline(always)]
+    pub fn tiles(&self) -> impl Iterator<Item = [(&V, &[V]); TILE]> {
+        (0..self.num_chunks / TILE).map(move |t| std::array::from_fn(|i| self.chunk(t * TILE + i)))
+    }
+}
+
+// the product kernels can work on the column chunks in tiles of `TILE`, each chunk
+// of a tile with its own accumulator, and then on any chunks left over one at a
+// time. Each input value is broadcast once per tile rather than once per chunk, and
+// the adds for the chunks are independent, rather than each one waiting on the
+// last. That only pays with enough inputs: with fewer, each chunk's chain of adds is
+// short, the CPU overlaps one chunk with the next anyway, and setting up the tiles
+// costs more than it saves. Tiles of two don't pay for themselves until there are
+// a lot more inputs again, so there's just the one size.
+pub const TILE: usize = 4;
+pub const TILE_MIN_ROWS: usize = 16;
+
+// the arithmetic an f32 backend supplies for the product kernels below, which are
+// written once for all of them: each backend implements this for its intrinsic
+// type. The methods are unsafe because the x86 ones are compiled for their target
+// feature, which the caller must check is there; the kernels are
+// `#[inline(always)]`, and each backend calls them from a function compiled for
+// that feature, so the intrinsics are inlined there.
+#[allow(clippy::missing_safety_doc)] // as above
+pub trait KernelF32: Lanes<Elem = f32> {
+    // an input value ready to multiply a row by: broadcast to every lane, or for
+    // NEON, whose multiply-add takes it from a lane, just the value
+    type Value: Copy;
+
+    unsafe fn broadcast(value: f32) -> Self::Value;
+
+    // accumulate += row * value
+    unsafe fn multiply_add(accumulate: &mut Self, row: Self, value: Self::Value);
+
+    // the approximate exponential of each lane
+    unsafe fn exp(v: Self) -> Self;
+
+    // read `src` into the first `src.len()` lanes, up to all of them; the rest are zero
+    unsafe fn load(src: &[f32]) -> Self;
+
+    // write the first `dst.len()` lanes, up to all of them
+    unsafe fn store(dst: &mut [f32], v: Self);
+}
+
+// write one chunk's outputs to `dst`, which is shorter than a chunk for the final
+// one: as they are, or for the softmax, their approximate exponentials added to the
+// running sum
+#[inline(always)]
+unsafe fn write_output<V: KernelF32>(
+    dst: &mut [f32],
+    accumulate: V,
+    softmax: bool,
+    cumulative_sum: &mut f32,
+) {
+    if softmax {
+        V::store(dst, V::exp(accumulate));
+        dst.iter_mut().for_each(|d| {
+            *cumulative_sum += *d;
+            *d = *cumulative_sum;
+        });
+    } else {
+        V::store(dst, accumulate);
+    }
+}
+
+// the product kernels; the lengths (and sparse indices) are checked by the caller
+impl<V: KernelF32> PackedColumns<V> {
+    // all the outputs of one column chunk
+    #[inline(always)]
+    unsafe fn chunk_product(values: &[f32], intercepts: &V, rows: &[V]) -> V {
+        let mut accumulate = *intercepts;
+        for (value, row) in values.iter().zip(rows) {
+            V::multiply_add(&mut accumulate, *row, V::broadcast(*value));
+        }
+        accumulate
+    }
+
+    // the product, or the cumulative softmax (not normalised), for one input vector
+    #[inline(always)]
+    pub(crate) unsafe fn product(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        if self.tiled() {
+            self.product_tiled(values, destination, softmax)
+        } else {
+            self.product_untiled(values, destination, softmax)
+        }
+    }
+
+    // a chunk at a time
+    #[inline(always)]
+    unsafe fn product_untiled(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        let mut cumulative_sum = 0f32;
+        for (dst, (intercepts, rows)) in destination.chunks_mut(V::LANES).zip(self.chunks()) {
+            let accumulate = Self::chunk_product(values, intercepts, rows);
+            write_output(dst, accumulate, softmax, &mut cumulative_sum);
+        }
+    }
+
+    // in tiles of column chunks, for shapes where that pays; see `TILE_MIN_ROWS`.
+    // Within a chunk the order of the adds is the same as for `product_untiled`, so
+    // the results are too.
+    #[inline(always)]
+    unsafe fn product_tiled(&self, values: &[f32], destination: &mut [f32], softmax: bool) {
+        let mut cumulative_sum = 0f32;
+
+        let outputs = destination.chunks_mut(V::LANES * TILE);
+        let mut chunk = 0;
+        for (out, tile) in outputs.zip(self.tiles()) {
+            // run multiplication and add to `accumulate`, starting with the intercepts
+            let mut accumulate = tile.map(|(intercepts, _)| *intercepts);
+            for (r, value) in values.iter().enumerate() {
+                // the broadcast is shared by the tile
+                let value = V::broadcast(*value);
+                for (acc, (_, rows)) in accumulate.iter_mut().zip(&tile) {
+                    // within bounds: each chunk has one intrinsic per input
+                    V::multiply_add(acc, *rows.get_unchecked(r), value);
+                }
+            }
+
+            for (dst, acc) in out.chunks_mut(V::LANES).zip(accumulate) {
+                write_output(dst, acc, softmax, &mut cumulative_sum);
+            }
+            chunk += TILE;
+        }
+
+        // the chunks left over, one at a time
+        for chunk in chunk..self.num_chunks {
+            let (intercepts, rows) = self.chunk(chunk);
+            let start = chunk * V::LANES;
+            let end = (start + V::LANES).min(self.num_outputs);
+            let accumulate = Self::chunk_product(values, intercepts, rows);
+            write_output(
+                &mut destination[start..end],
+                accumulate,
+                softmax,
+                &mut cumulative_sum,
+            );
+        }
+    }
+
+    // `inputs` holds input vectors one after the other, and `outputs` receives the
+    // output vectors in the same way. Runs `BATCH_BLOCK` input vectors at a time,
+    // so each coefficient intrinsic is loaded once and used for every input in the
+    // block, and there are several independent accumulators in flight instead of one.
+    #[inline(always)]
+    pub(crate) unsafe fn product_batch(&self, inputs: &[f32], outputs: &mut [f32], softmax: bool) {
+        let num_inputs = self.num_rows();
+        let num_outputs = self.num_outputs;
+        if num_outputs == 0 {
+            return;
+        }
+
+        let mut input_blocks = inputs.chunks_exact(num_inputs * BATCH_BLOCK);
+        let mut output_blocks = outputs.chunks_exact_mut(num_outputs * BATCH_BLOCK);
+        for (inp, out) in (&mut input_blocks).zip(&mut output_blocks) {
+            let mut cumulative_sum = [0f32; BATCH_BLOCK];
+
+            for (chunk_num, (intercepts, rows)) in self.chunks().enumerate() {
+                // run multiplication and add to `accumulate`, starting with the intercepts
+                let mut accumulate = [*intercepts; BATCH_BLOCK];
+                for (r, row) in rows.iter().enumerate() {
+                    for (b, acc) in accumulate.iter_mut().enumerate() {
+                        // within bounds: `inp` is exactly BATCH_BLOCK input vectors
+                        let value = *inp.get_unchecked(b * num_inputs + r);
+                        V::multiply_add(acc, *row, V::broadcast(value));
+                    }
+                }
+
+                // each to its destination, taking into account the final shorter stub
+                let start = chunk_num * V::LANES;
+                let len = V::LANES.min(num_outputs - start);
+                for (b, acc) in accumulate.iter().enumerate() {
+                    let dst = &mut out[b * num_outputs + start..][..len];
+                    write_output(dst, *acc, softmax, &mut cumulative_sum[b]);
+                }
+            }
+        }
+
+        // whatever is left over doesn't fill a block; do these one at a time
+        for (inp, out) in input_blocks
+            .remainder()
+            .chunks_exact(num_inputs)
+            .zip(output_blocks.into_remainder().chunks_exact_mut(num_outputs))
+        {
+            self.product_untiled(inp, out, softmax);
+        }
+    }
+
+    // sparse input: only the rows listed in `indices` are non-zero, holding the
+    // matching `values`; indices must all be less than `num_rows`
+    #[inline(always)]
+    pub(crate) unsafe fn product_sparse(
+        &self,
+        indices: &[u32],
+        values: &[f32],
+        destination: &mut [f32],
+        softmax: bool,
+    ) {
+        let mut cumulative_sum = 0f32;
+        for (dst, (intercepts, rows)) in destination.chunks_mut(V::LANES).zip(self.chunks()) {
+            // accumulate just the listed rows, starting with the intercepts
+            let mut accumulate = *intercepts;
+            for (index, value) in indices.iter().zip(values) {
+                let row = *rows.get_unchecked(*index as usize);
+                V::multiply_add(&mut accumulate, row, V::broadcast(*value));
+            }
+            write_output(dst, accumulate, softmax, &mut cumulative_sum);
+        }
+    }
+
+    // add `delta` times one row's coefficients to `destination`, which has one value
+    // per output: each chunk of it is loaded, has the row multiply-added, and is
+    // stored back
+    #[inline(always)]
+    pub(crate) unsafe fn add_scaled_row(
+        &self,
+        row: usize,
+        delta: f32,
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        self.check_row(row)?;
+        if destination.len() != self.num_outputs {
+            return Err(EstimatorError::OutputLength {
+                expected: self.num_outputs,
+                actual: destination.len(),
+            });
+        }
+
+        let value = V::broadcast(delta);
+        for (dst, (_, rows)) in destination.chunks_mut(V::LANES).zip(self.chunks()) {
+            let mut accumulate = V::load(dst);
+            // within bounds: checked above
+            V::multiply_add(&mut accumulate, *rows.get_unchecked(row), value);
+            V::store(dst, accumulate);
+        }
+        Ok(())
+    }
+
+    // the outputs at `indices`, in that order: only the chunks covering the indices
+    // are computed, and a chunk just once for a run of indices in it, so sorted
+    // indices are best
+    #[inline(always)]
+    pub(crate) unsafe fn product_selected(
+        &self,
+        values: &[f32],
+        indices: &[usize],
+        destination: &mut [f32],
+    ) -> Result<(), EstimatorError> {
+        if let Some(&index) = indices.iter().find(|i| **i >= self.num_outputs) {
+            return Err(EstimatorError::OutputIndex {
+                index,
+                num_outputs: self.num_outputs,
+            });
+        }
+
+        let mut current = None;
+        let mut accumulate = zeros::<V>();
+        for (d, &index) in destination.iter_mut().zip(indices) {
+            let chunk = index / V::LANES;
+            if current != Some(chunk) {
+                let (intercepts, rows) = self.chunk(chunk);
+                accumulate = Self::chunk_product(values, intercepts, rows);
+                current = Some(chunk);
+            }
+            *d = as_slice(&accumulate)[index % V::LANES];
+        }
+        Ok(())
+    }
+}
+
+// the in-place updates, for a matrix of `$elem` with its coefficients in a
+// `columns: PackedColumns<_>` field and the usual `num_rows` / `num_columns`
+macro_rules! impl_updates {
+    ($elem:ty) => {
+        // replace all of the intercepts
+        pub fn set_intercepts(
+            &mut self,
+            intercepts: &[$elem],
+        ) -> Result<(), $crate::error::EstimatorError> {
+            self.columns.set_intercepts(intercepts)
+        }
+
+        // shift each intercept by the matching delta
+        pub fn add_to_intercepts(
+            &mut self,
+            deltas: &[$elem],
+        ) -> Result<(), $crate::error::EstimatorError> {
+            self.columns.add_to_intercepts(deltas)
+        }
+
+        // replace the coefficients of one input, one per output
+        pub fn set_input_row(
+            &mut self,
+            input: usize,
+            coefficients: &[$elem],
+        ) -> Result<(), $crate::error::EstimatorError> {
+            self.columns.set_row(input, coefficients)
+        }
+
+        pub fn set_coefficient(
+            &mut self,
+            input: usize,
+            output: usize,
+            value: $elem,
+        ) -> Result<(), $crate::error::EstimatorError> {
+            self.columns.set_coefficient(input, output, value)
+        }
+    };
+}
+
+pub(crate) use impl_updates;
+
+// reading the coefficients back out, likewise; the padding lanes are skipped
+macro_rules! impl_read_back {
+    ($elem:ty) => {
+        pub fn coefficient(&self, input: usize, output: usize) -> Option<$elem> {
+            self.columns.coefficient(input, output)
+        }
+
+        pub fn intercepts(&self) -> Vec<$elem> {
+            self.columns.intercepts()
+        }
+
+        // the same shape as the `rows` the matrix was created from
+        pub fn to_rows(&self) -> Vec<Vec<$elem>> {
+            self.columns.rows()
+        }
+
+        // (input, output, coefficient) for every coefficient, an input at a time
+        pub fn coefficients(&self) -> impl Iterator<Item = (usize, usize, $elem)> + '_ {
+            self.columns.coefficients()
+        }
+    };
+}
+
+pub(crate) use impl_read_back;
+
+#[cfg(test)]
+mod tests {
+    use super::{Coefficients, PackedColumns};
+    use crate::error::EstimatorError;
+
+    #[test]
+    fn packed_layout() {
+        let rows = vec![vec![1f32, 2., 3., 4., 5.], vec![6f32, 7., 8., 9., 10.]];
+        let intercepts = [0.1f32, 0.2, 0.3, 0.4, 0.5];
+        let packed = PackedColumns::<[f32; 4]>::from_rows(&rows, &intercepts).unwrap();
+
+        assert_eq!(packed.num_chunks(), 2);
+        assert_eq!(packed.data.len(), 2 * 3);
+
+        let chunks: Vec<_> = packed.chunks().collect();
+        assert_eq!(*chunks[0].0, [0.1f32, 0.2, 0.3, 0.4]);
+        assert_eq!(chunks[0].1, [[1f32, 2., 3., 4.], [6f32, 7., 8., 9.]]);
+        // final chunk is zero padded
+        assert_eq!(*chunks[1].0, [0.5f32, 0., 0., 0.]);
+        assert_eq!(chunks[1].1, [[5f32, 0., 0., 0.], [10f32, 0., 0., 0.]]);
+
+        // ragged rows are rejected
+        let ragged = vec![vec![1f32, 2., 3., 4., 5.], vec![6f32]];
+        assert_eq!(
+            PackedColumns::<[f32; 4]>::from_rows(&ragged, &intercepts).err(),
+            Some(EstimatorError::RaggedRow {
+                row: 1,
+                expected: 5,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            PackedColumns::<[f32; 4]>::from_rows(&rows, &intercepts[1..]).err(),
+            Some(EstimatorError::InterceptLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            PackedColumns::<[f32; 4]>::from_rows(&[], &intercepts).err(),
+            Some(EstimatorError::NoInputs)
+        );
+        let short = Coefficients::RowMajor {
+            data: &[1f32; 9],
+            num_inputs: 2,
+            num_outputs: 5,
+        };
+        assert_eq!(
+            short.dimensions(),
+            Err(EstimatorError::CoefficientLength {
+                expected: 10,
+                actual: 9
+            })
+        );
+    }
+
+    #[test]
+    fn packed_updates() {
+        let rows = vec![vec![1f32, 2., 3., 4., 5.], vec![6f32, 7., 8., 9., 10.]];
+        let mut packed = PackedColumns::<[f32; 4]>::from_rows(&rows, &[0f32; 5]).unwrap();
+
+        packed.set_intercepts(&[1f32, 2., 3., 4., 5.]).unwrap();
+        packed.add_to_intercepts(&[0.5f32; 5]).unwrap();
+        packed.set_row(1, &[-1f32, -2., -3., -4., -5.]).unwrap();
+        packed.set_coefficient(0, 4, 50.).unwrap();
+
+        let chunks: Vec<_> = packed.chunks().collect();
+        assert_eq!(*chunks[0].0, [1.5f32, 2.5, 3.5, 4.5]);
+        assert_eq!(chunks[0].1, [[1f32, 2., 3., 4.], [-1f32, -2., -3., -4.]]);
+        // padding is untouched
+        assert_eq!(*chunks[1].0, [5.5f32, 0., 0., 0.]);
+        assert_eq!(chunks[1].1, [[50f32, 0., 0., 0.], [-5f32, 0., 0., 0.]]);
+
+        // out of range, or the wrong length
+        assert!(packed.set_intercepts(&[0f32; 4]).is_err());
+        assert!(packed.add_to_intercepts(&[0f32; 6]).is_err());
+        assert!(packed.set_row(1, &[0f32; 4]).is_err());
+        assert_eq!(
+            packed.set_row(2, &[0f32; 5]),
+            Err(EstimatorError::InputIndex {
+                index: 2,
+                num_inputs: 2
+            })
+        );
+        assert_eq!(
+            packed.set_coefficient(0, 5, 1.),
+            Err(EstimatorError::OutputIndex {
+                index: 5,
+                num_outputs: 5
+            })
+        );
+        assert!(packed.set_coefficient(2, 0, 1.).is_err());
+    }
+
+    #[test]
+    fn packed_read_back() {
+        let rows = vec![vec![1f32, 2., 3., 4., 5.], vec![6f32, 7., 8., 9., 10.]];
+        let intercepts = [0.1f32, 0.2, 0.3, 0.4, 0.5];
+        let packed = PackedColumns::<[f32; 4]>::from_rows(&rows, &intercepts).unwrap();
+
+        assert_eq!(packed.intercepts(), intercepts);
+        assert_eq!(packed.rows(), rows);
+        assert_eq!(packed.coefficient(1, 4), Some(10.));
+        assert_eq!(packed.coefficient(2, 0), None);
+        assert_eq!(packed.coefficient(0, 5), None);
+
+        let all: Vec<_> = packed.coefficients().collect();
+        assert_eq!(all.len(), 10);
+        assert_eq!(all[0], (0, 0, 1.));
+        assert_eq!(all[4], (0, 4, 5.));
+        assert_eq!(all[5], (1, 0, 6.));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn packed_alignment() {
+        use std::arch::x86_64::*;
+
+        let rows = vec![vec![1f32; 35]; 3];
+        let intercepts = [0f32; 35];
+        let avx = PackedColumns::<__m256>::from_rows(&rows, &intercepts).unwrap();
+        assert_eq!(avx.data.as_ptr() as usize % 32, 0);
+        let avx512 = PackedColumns::<__m512>::from_rows(&rows, &intercepts).unwrap();
+        assert_eq!(avx512.data.as_ptr() as usize % 64, 0);
+    }
+}
@@ -0,0 +1,42 @@
+use rayon::prelude::*;
+
+use crate::batch::{self, BATCH_BLOCK};
+use crate::error::EstimatorError;
+use crate::estimator::LinearEstimator;
+
+// a reasonable number of rows per task if you don't have a better idea; much
+// smaller than this and the threading overhead outweighs the work
+pub const DEFAULT_MIN_CHUNK_ROWS: usize = 256;
+
+// split a batch into chunks of at least `min_chunk_rows` rows, and run `f` (one
+// of the sequential batch functions) on each chunk across the rayon thread pool.
+// Each row is computed exactly as it would be sequentially, so the results are
+// identical.
+pub(crate) fn par_batch<E, F>(
+    estimator: &E,
+    inputs: &[f32],
+    n_rows: usize,
+    outputs: &mut [f32],
+    min_chunk_rows: usize,
+    f: F,
+) -> Result<(), EstimatorError>
+where
+    E: LinearEstimator + ?Sized,
+    F: Fn(&E, &[f32], usize, &mut [f32]) -> Result<(), EstimatorError> + Sync,
+{
+    let num_inputs = estimator.num_inputs();
+    let num_outputs = estimator.num_outputs();
+    batch::check_dimensions(inputs, n_rows, outputs, num_inputs, num_outputs)?;
+    if n_rows == 0 || num_outputs == 0 {
+        return Ok(());
+    }
+
+    // round up to whole blocks, so the SIMD kernels only drop to single rows at the
+    // very end of the batch
+    let chunk_rows = min_chunk_rows.max(1).div_ceil(BATCH_BLOCK) * BATCH_BLOCK;
+
+    inputs
+        .par_chunks(chunk_rows * num_inputs)
+        .zip(outputs.par_chunks_mut(chunk_rows * num_outputs))
+        .try_for_each(|(inp, out)| f(estimator, inp, inp.len() / num_inputs, out))
+}
@@ -0,0 +1,122 @@
+// int8 quantization of the coefficients, shared by the `MatrixI8` implementations
+//
+// each output (column) gets its own scale and zero point, chosen from the range of
+// its coefficients (always including zero, so zeros stay exact):
+//
+//      coefficient = scale * (q - zero_point),     q in -128..=127
+//
+// the product then only needs the plain sum of `value * q` for each column, with the
+// scale and zero point applied once at the end:
+//
+//      y = intercept + scale * sum(value * q) - scale * zero_point * sum(value)
+//
+// the quantized values are stored the same way as the dense f32 coefficients,
+// column chunk major: for each chunk of 8 outputs, one `[i8; 8]` per input row.
+
+use crate::error::{check_intercepts, row_dimensions, EstimatorError};
+
+pub const BYTES_PER_CHUNK: usize = 8;
+
+pub type QuantizedLanes = [i8; BYTES_PER_CHUNK];
+pub type Lanes = [f32; BYTES_PER_CHUNK];
+
+pub struct QuantizedColumns {
+    pub num_columns: usize,
+    pub num_rows: usize,
+    coefficients: Vec<QuantizedLanes>,
+    scales: Vec<Lanes>,
+    // scale * zero_point, to be multiplied by the sum of the inputs
+    scaled_zero_points: Vec<Lanes>,
+    intercepts: Vec<Lanes>,
+}
+
+// the per column parameters, from the range of the coefficients
+fn scale_and_zero_point(column: impl Iterator<Item = f32>) -> (f32, i32) {
+    let (min, max) = column.fold((0f32, 0f32), |(lo, hi), c| (lo.min(c), hi.max(c)));
+    let scale = (max - min) / 255.0;
+    if scale <= 0.0 {
+        // all zero; keep the zero point at zero too, so the output is exactly the intercept
+        return (1.0, 0);
+    }
+    let zero_point = (-128.0 - min / scale).round().clamp(-128.0, 127.0) as i32;
+    (scale, zero_point)
+}
+
+fn quantize_value(c: f32, scale: f32, zero_point: i32) -> i8 {
+    ((c / scale).round() as i32 + zero_point).clamp(-128, 127) as i8
+}
+
+fn to_lanes(values: &[f32]) -> Lanes {
+    let mut lanes = [0f32; BYTES_PER_CHUNK];
+    lanes[..values.len()].copy_from_slice(values);
+    lanes
+}
+
+impl QuantizedColumns {
+    pub fn from_rows(rows: &[Vec<f32>], intercepts: &[f32]) -> Result<Self, EstimatorError> {
+        let (_, num_columns) = row_dimensions(rows)?;
+        check_intercepts(intercepts.len(), num_columns)?;
+
+        let params: Vec<(f32, i32)> = (0..num_columns)
+            .map(|o| scale_and_zero_point(rows.iter().map(|r| r[o])))
+            .collect();
+        let scales: Vec<f32> = params.iter().map(|(s, _)| *s).collect();
+        let scaled_zero_points: Vec<f32> = params.iter().map(|(s, z)| s * *z as f32).collect();
+
+        let mut coefficients =
+            Vec::with_capacity(num_columns.div_ceil(BYTES_PER_CHUNK) * rows.len());
+        for (chunk_num, chunk_params) in params.chunks(BYTES_PER_CHUNK).enumerate() {
+            let start = chunk_num * BYTES_PER_CHUNK;
+            for r in rows {
+                let mut lanes = [0i8; BYTES_PER_CHUNK];
+                for (lane, (c, (scale, zero_point))) in
+                    lanes.iter_mut().zip(r[start..].iter().zip(chunk_params))
+                {
+                    *lane = quantize_value(*c, *scale, *zero_point);
+                }
+                coefficients.push(lanes);
+            }
+        }
+
+        Ok(Self {
+            num_columns,
+            num_rows: rows.len(),
+            coefficients,
+            scales: scales.chunks(BYTES_PER_CHUNK).map(to_lanes).collect(),
+            scaled_zero_points: scaled_zero_points
+                .chunks(BYTES_PER_CHUNK)
+                .map(to_lanes)
+                .collect(),
+            intercepts: intercepts.chunks(BYTES_PER_CHUNK).map(to_lanes).collect(),
+        })
+    }
+
+    // the coefficient as it will be used, after quantizing
+    pub fn dequantized(&self, input: usize, output: usize) -> f32 {
+        let chunk = output / BYTES_PER_CHUNK;
+        let lane = output % BYTES_PER_CHUNK;
+        let q = self.coefficients[chunk * self.num_rows + input][lane];
+        self.scales[chunk][lane] * q as f32 - self.scaled_zero_points[chunk][lane]
+    }
+
+    // (quantized rows, scales, scaled zero points, intercepts) for each column
+    // chunk in turn
+    #[inline(always)]
+    pub fn chunks(&self) -> impl Iterator<Item = (&[QuantizedLanes], &Lanes, &Lanes, &Lanes)> {
+        self.coefficients
+            .chunks_exact(self.num_rows)
+            .zip(&self.scales)
+            .zip(&self.scaled_zero_points)
+            .zip(&self.intercepts)
+            .map(|(((col, s), z), i)| (col, s, z, i))
+    }
+}
+
+// how far the quantized matrix is from the original
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantizationReport {
+    // largest difference between an original and a dequantized coefficient
+    pub max_coefficient_error: f32,
+    // largest difference in `product` output over the sample inputs
+    pub max_abs_error: f32,
+}
@@ -0,0 +1,115 @@
+use crate::error::EstimatorError;
+use crate::exp_approx::exp_approx_f32;
+use crate::matrix_dispatch::MatrixF32;
+
+// re-scoring for inputs that change a few at a time, e.g. in a simulation: the
+// linear outputs for the current inputs are kept, and changing an input adds the
+// change times that input's coefficients to them, rather than computing the whole
+// product again. Every `refresh_interval` changes the outputs are recomputed from
+// scratch, so the rounding from the updates can't build up.
+pub struct Scorer<'a> {
+    matrix: &'a MatrixF32,
+    values: Vec<f32>,
+    outputs: Vec<f32>,
+    // zero for never
+    refresh_interval: usize,
+    since_refresh: usize,
+}
+
+impl<'a> Scorer<'a> {
+    pub const DEFAULT_REFRESH_INTERVAL: usize = 64;
+
+    // scores `values` to start from
+    pub fn create(matrix: &'a MatrixF32, values: &[f32]) -> Result<Self, EstimatorError> {
+        Self::create_with_refresh(matrix, values, Self::DEFAULT_REFRESH_INTERVAL)
+    }
+
+    // recomputing every `refresh_interval` changes, or with zero, only when
+    // `refresh` is called
+    pub fn create_with_refresh(
+        matrix: &'a MatrixF32,
+        values: &[f32],
+        refresh_interval: usize,
+    ) -> Result<Self, EstimatorError> {
+        let mut outputs = vec![0f32; matrix.num_columns];
+        matrix.product(values, &mut outputs)?;
+        Ok(Self {
+            matrix,
+            values: values.to_vec(),
+            outputs,
+            refresh_interval,
+            since_refresh: 0,
+        })
+    }
+
+    // the current inputs
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    // the linear outputs for the current inputs, as `product` would give
+    pub fn outputs(&self) -> &[f32] {
+        &self.outputs
+    }
+
+    // cumulative sum of the approximate exponential of the outputs (not normalised),
+    // as `product_softmax_cumulative_approx` would give
+    pub fn softmax_cumulative_approx(&self, destination: &mut [f32]) -> Result<(), EstimatorError> {
+        if destination.len() != self.outputs.len() {
+            return Err(EstimatorError::OutputLength {
+                expected: self.outputs.len(),
+                actual: destination.len(),
+            });
+        }
+        let mut cumulative_sum = 0f32;
+        for (d, o) in destination.iter_mut().zip(&self.outputs) {
+            cumulative_sum += exp_approx_f32(*o);
+            *d = cumulative_sum;
+        }
+        Ok(())
+    }
+
+    pub fn set_input(&mut self, input: usize, value: f32) -> Result<(), EstimatorError> {
+        let current = self
+            .values
+            .get_mut(input)
+            .ok_or(EstimatorError::InputIndex {
+                index: input,
+                num_inputs: self.matrix.num_rows,
+            })?;
+        let delta = value - *current;
+        *current = value;
+
+        self.since_refresh += 1;
+        // a change to or from a non-finite value can't be undone by another update,
+        // so those are recomputed too
+        if self.since_refresh == self.refresh_interval || !delta.is_finite() {
+            self.refresh()
+        } else {
+            self.matrix
+                .add_scaled_input_row(input, delta, &mut self.outputs)
+        }
+    }
+
+    // several (input, value) changes
+    pub fn set_inputs(&mut self, changes: &[(usize, f32)]) -> Result<(), EstimatorError> {
+        for &(input, value) in changes {
+            self.set_input(input, value)?;
+        }
+        Ok(())
+    }
+
+    // all new inputs, so the outputs are recomputed
+    pub fn set_values(&mut self, values: &[f32]) -> Result<(), EstimatorError> {
+        self.matrix.product(values, &mut self.outputs)?;
+        self.values.copy_from_slice(values);
+        self.since_refresh = 0;
+        Ok(())
+    }
+
+    // recompute the outputs from scratch for the current inputs
+    pub fn refresh(&mut self) -> Result<(), EstimatorError> {
+        self.since_refresh = 0;
+        self.matrix.product(&self.values, &mut self.outputs)
+    }
+}
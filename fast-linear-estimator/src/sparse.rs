@@ -0,0 +1,36 @@
+// helpers shared by the sparse-input products
+//
+// a sparse input is a list of input (row) indices and the values at those rows;
+// every row not listed is zero, so only the listed rows are accumulated on top of
+// the intercepts. Indices can be in any order, and a repeated index adds up.
+
+use crate::error::EstimatorError;
+
+// check the sparse inputs, and the length of the destination
+pub(crate) fn check_inputs(
+    indices: &[u32],
+    values: &[f32],
+    destination: usize,
+    num_inputs: usize,
+    num_outputs: usize,
+) -> Result<(), EstimatorError> {
+    if indices.len() != values.len() {
+        return Err(EstimatorError::SparseLength {
+            indices: indices.len(),
+            values: values.len(),
+        });
+    }
+    if let Some(&index) = indices.iter().find(|&&i| i as usize >= num_inputs) {
+        return Err(EstimatorError::InputIndex {
+            index: index as usize,
+            num_inputs,
+        });
+    }
+    if destination != num_outputs {
+        return Err(EstimatorError::OutputLength {
+            expected: num_outputs,
+            actual: destination,
+        });
+    }
+    Ok(())
+}